@@ -0,0 +1,175 @@
+//! Bech32 and EVM address checks
+use bech32::{Bech32, Hrp};
+use web3::signing::keccak256;
+
+/// Whether `address` looks like an EVM address rather than a bech32 one.
+pub fn is_hex_address(address: &str) -> bool {
+    address.starts_with("0x") || address.starts_with("0X")
+}
+
+/// Check the bech32 checksum of `address` and, when given, its human readable prefix.
+pub fn check_bech32(address: &str, prefix: Option<&str>) -> Result<(), String> {
+    let (hrp, data) = bech32::decode(address)
+        .map_err(|e| format!("'{}' is not a valid bech32 address: {}", address, e))?;
+    if let Some(prefix) = prefix {
+        let expected = Hrp::parse(prefix)
+            .map_err(|e| format!("bech32_prefix '{}' is invalid: {}", prefix, e))?;
+        if hrp != expected {
+            return Err(format!(
+                "'{}' has prefix '{}', expected '{}'",
+                address, hrp, prefix
+            ));
+        }
+    }
+    if data.len() != 20 && data.len() != 32 {
+        return Err(format!(
+            "'{}' holds {} bytes, expected 20 or 32",
+            address,
+            data.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Check that `address` is a 20-byte hex address whose mixed case, if any,
+/// matches its EIP-55 checksum. All-lowercase and all-uppercase addresses
+/// carry no checksum and are accepted.
+pub fn check_eip55(address: &str) -> Result<(), String> {
+    let hex = address
+        .strip_prefix("0x")
+        .ok_or_else(|| format!("'{}' does not start with 0x", address))?;
+    if hex.len() != 40 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("'{}' is not a 20-byte hex address", address));
+    }
+    let is_lower = hex.chars().all(|c| !c.is_ascii_uppercase());
+    let is_upper = hex.chars().all(|c| !c.is_ascii_lowercase());
+    if is_lower || is_upper {
+        return Ok(());
+    }
+    let checksummed = to_checksum_address(hex);
+    if checksummed != address {
+        return Err(format!(
+            "'{}' fails the EIP-55 checksum, expected '{}'",
+            address, checksummed
+        ));
+    }
+    Ok(())
+}
+
+/// The 0x form of a 20-byte bech32 address, as used by the EVM side of
+/// ethsecp256k1 chains such as MANTRA.
+pub fn bech32_to_hex(address: &str) -> Result<String, String> {
+    let (_, data) = bech32::decode(address)
+        .map_err(|e| format!("'{}' is not a valid bech32 address: {}", address, e))?;
+    if data.len() != 20 {
+        return Err(format!(
+            "'{}' holds {} bytes, only 20-byte addresses have a hex form",
+            address,
+            data.len()
+        ));
+    }
+    let hex = data
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect::<String>();
+    Ok(to_checksum_address(&hex))
+}
+
+/// The bech32 form with `prefix` of a 0x address.
+pub fn hex_to_bech32(address: &str, prefix: &str) -> Result<String, String> {
+    let hex = address
+        .strip_prefix("0x")
+        .or_else(|| address.strip_prefix("0X"))
+        .ok_or_else(|| format!("'{}' does not start with 0x", address))?;
+    if hex.len() != 40 {
+        return Err(format!("'{}' is not a 20-byte hex address", address));
+    }
+    let data = (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&hex[i..i + 2], 16))
+        .collect::<Result<Vec<u8>, _>>()
+        .map_err(|_| format!("'{}' is not a 20-byte hex address", address))?;
+    let hrp =
+        Hrp::parse(prefix).map_err(|e| format!("bech32_prefix '{}' is invalid: {}", prefix, e))?;
+    bech32::encode::<Bech32>(hrp, &data).map_err(|e| e.to_string())
+}
+
+/// EIP-55 mixed-case form of the 40 hex digits `hex`, with the `0x` prefix.
+pub fn to_checksum_address(hex: &str) -> String {
+    let hex = hex.to_ascii_lowercase();
+    let hash = keccak256(hex.as_bytes());
+    let checksummed = hex
+        .chars()
+        .enumerate()
+        .map(|(i, c)| {
+            let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+            if nibble >= 8 {
+                c.to_ascii_uppercase()
+            } else {
+                c
+            }
+        })
+        .collect::<String>();
+    format!("0x{}", checksummed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_bech32() {
+        let address = "mantra1x5nk33zpglp4ge6q9a8xx3zceqf4g8nvaggjmc";
+        assert!(check_bech32(address, None).is_ok());
+        assert!(check_bech32(address, Some("mantra")).is_ok());
+        assert!(check_bech32(
+            "mantra1wrvwhcfuhqe7eru59ehkxxr2e262ksnzhtfmdtr96wctr8m2kafq2vh64r",
+            Some("mantra")
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_bech32() {
+        // last character changed
+        let typo = "mantra1x5nk33zpglp4ge6q9a8xx3zceqf4g8nvaggjmd";
+        assert!(check_bech32(typo, None).is_err());
+        let address = "mantra1x5nk33zpglp4ge6q9a8xx3zceqf4g8nvaggjmc";
+        assert!(check_bech32(address, Some("cosmos")).is_err());
+    }
+
+    #[test]
+    fn checks_eip55() {
+        assert!(check_eip55("0xAb5801a7D398351b8bE11C439e05C5B3259aeC9B").is_ok());
+        assert!(check_eip55("0xab5801a7d398351b8be11c439e05c5b3259aec9b").is_ok());
+        assert!(check_eip55("0xAB5801A7D398351B8BE11C439E05C5B3259AEC9B").is_ok());
+        assert!(check_eip55("0xAb5801a7D398351b8bE11C439e05C5B3259aec9B").is_err());
+        assert!(check_eip55("0xAb5801a7D398351b8bE11C439e05C5B3259aeC9").is_err());
+        assert!(check_eip55("Ab5801a7D398351b8bE11C439e05C5B3259aeC9B").is_err());
+    }
+
+    #[test]
+    fn converts_between_bech32_and_hex() {
+        let bech32 = "mantra1x5nk33zpglp4ge6q9a8xx3zceqf4g8nvaggjmc";
+        let hex = bech32_to_hex(bech32).unwrap();
+        assert!(check_eip55(&hex).is_ok());
+        assert_eq!(hex_to_bech32(&hex, "mantra").unwrap(), bech32);
+        assert_eq!(
+            hex_to_bech32(&hex.to_lowercase(), "cosmos").unwrap(),
+            "cosmos1x5nk33zpglp4ge6q9a8xx3zceqf4g8nvkrzkcz"
+        );
+        // contract addresses are 32 bytes and have no hex form
+        assert!(
+            bech32_to_hex("mantra1wrvwhcfuhqe7eru59ehkxxr2e262ksnzhtfmdtr96wctr8m2kafq2vh64r")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn computes_checksum_address() {
+        assert_eq!(
+            to_checksum_address("d2b95283011e47257917770d28bb3ee44c849f6f"),
+            "0xd2b95283011E47257917770D28Bb3EE44c849f6F"
+        );
+    }
+}
@@ -0,0 +1,129 @@
+//! Alert state of one coin, with hysteresis and a pending period
+use crate::handle::CoinEntity;
+use std::time::Instant;
+use web3::types::U256;
+
+/// Where the alert of a coin stands, exported as `account_alert_state`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AlertState {
+    /// Above `min_balance`.
+    #[default]
+    Ok = 0,
+    /// At or below `min_balance` for less than `for`.
+    Pending = 1,
+    /// At or below `min_balance` for `for`, until above `clear_balance`.
+    Firing = 2,
+    /// Back above `clear_balance` since the last refresh.
+    Resolved = 3,
+}
+
+impl AlertState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AlertState::Ok => "ok",
+            AlertState::Pending => "pending",
+            AlertState::Firing => "firing",
+            AlertState::Resolved => "resolved",
+        }
+    }
+
+    pub fn as_i64(&self) -> i64 {
+        *self as i64
+    }
+}
+
+/// Tracks the alert state of one coin across refreshes.
+#[derive(Clone, Debug, Default)]
+pub struct AlertTracker {
+    state: AlertState,
+    breached_since: Option<Instant>,
+}
+
+impl AlertTracker {
+    /// Move to the state of the balance `amount` of `coin` queried `at`.
+    pub fn update(&mut self, coin: &CoinEntity, amount: U256, at: Instant) -> AlertState {
+        let state = match self.state {
+            AlertState::Firing if amount <= coin.clear_balance => AlertState::Firing,
+            AlertState::Firing => AlertState::Resolved,
+            _ if amount > coin.min_balance => AlertState::Ok,
+            _ => {
+                let since = *self.breached_since.get_or_insert(at);
+                if at.duration_since(since) >= coin.for_duration {
+                    AlertState::Firing
+                } else {
+                    AlertState::Pending
+                }
+            }
+        };
+        if !matches!(state, AlertState::Pending | AlertState::Firing) {
+            self.breached_since = None;
+        }
+        self.state = state;
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::handle::coin_groups;
+    use std::time::Duration;
+
+    fn coin(thresholds: &str) -> CoinEntity {
+        let config = toml::from_str::<Config>(&format!(
+            "[[chains]]\nid = 'chain_A'\n[[chains.addresses]]\naddress = 'someone'\nrole = 'relayer'\n[[chains.addresses.coins]]\ndenom = 'uom'\n{}",
+            thresholds
+        ))
+        .unwrap();
+        coin_groups(&config.chains[0].addresses[0])
+            .remove(0)
+            .1
+            .remove(0)
+    }
+
+    /// The states reached by `balances` queried a minute apart.
+    fn states(coin: &CoinEntity, balances: &[u64]) -> Vec<AlertState> {
+        let start = Instant::now();
+        let mut tracker = AlertTracker::default();
+        balances
+            .iter()
+            .enumerate()
+            .map(|(minute, balance)| {
+                let at = start + Duration::from_secs(60) * minute as u32;
+                tracker.update(coin, U256::from(*balance), at)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fires_at_once_without_for() {
+        use AlertState::*;
+        let coin = coin("min_balance = '100'");
+        assert_eq!(
+            states(&coin, &[101, 100, 101, 101, 99]),
+            [Ok, Firing, Resolved, Ok, Firing]
+        );
+    }
+
+    #[test]
+    fn clear_balance_stops_flapping() {
+        use AlertState::*;
+        let coin = coin("min_balance = '100'\nclear_balance = '120'");
+        assert_eq!(
+            states(&coin, &[100, 101, 99, 120, 121, 101, 100]),
+            [Firing, Firing, Firing, Firing, Resolved, Ok, Firing]
+        );
+    }
+
+    #[test]
+    fn for_delays_firing() {
+        use AlertState::*;
+        let coin = coin("min_balance = '100'\nfor = '2m'");
+        assert_eq!(
+            states(&coin, &[100, 100, 101, 100, 100, 100, 101]),
+            [Pending, Pending, Ok, Pending, Pending, Firing, Resolved]
+        );
+        assert_eq!(Resolved.as_i64(), 3);
+    }
+}
@@ -0,0 +1,168 @@
+//! Arbitrary-precision token amounts
+use crate::error::Error;
+use std::fmt;
+use web3::types::U256;
+
+/// Balance of one denom as returned by a balance source, in atomic units.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Balance {
+    pub denom: String,
+    pub amount: U256,
+}
+
+/// Parse a decimal string of atomic units, e.g. a cosmos `Int` or a `min_balance`.
+pub fn parse_atomics(value: &str) -> Result<U256, Error> {
+    U256::from_dec_str(value).map_err(|_| Error::amount_parse(value.to_string()))
+}
+
+/// Parse a decimal string of whole units, e.g. `1.5` with 18 decimals, into
+/// atomic units. Fractional digits beyond `decimal_place` are rejected rather
+/// than rounded.
+pub fn parse_units(value: &str, decimal_place: u32) -> Result<U256, Error> {
+    let (integer, fraction) = value.split_once('.').unwrap_or((value, ""));
+    let is_digits = |digits: &str| digits.bytes().all(|b| b.is_ascii_digit());
+    if integer.is_empty() || !is_digits(integer) || !is_digits(fraction) {
+        return Err(Error::amount_parse(value.to_string()));
+    }
+    let fraction = fraction.trim_end_matches('0');
+    if fraction.len() > decimal_place as usize {
+        return Err(Error::amount_precision(value.to_string(), decimal_place));
+    }
+    let digits = format!(
+        "{}{:0<width$}",
+        integer,
+        fraction,
+        width = decimal_place as usize
+    );
+    parse_atomics(&digits).map_err(|_| Error::amount_parse(value.to_string()))
+}
+
+/// An atomic amount together with the decimal place of its denom, displayed
+/// losslessly in whole units (e.g. `400000000000000000` with 18 decimals is `0.4`).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Amount {
+    atomics: U256,
+    decimal_place: u32,
+}
+
+impl Amount {
+    pub fn new(atomics: U256, decimal_place: u32) -> Self {
+        Self {
+            atomics,
+            decimal_place,
+        }
+    }
+
+    pub fn atomics(&self) -> U256 {
+        self.atomics
+    }
+
+    pub fn decimal_place(&self) -> u32 {
+        self.decimal_place
+    }
+
+    /// Rounds down to at most `precision` fractional digits. `None` keeps all of them.
+    pub fn with_precision(&self, precision: Option<u32>) -> Self {
+        match precision {
+            Some(precision) if precision < self.decimal_place => {
+                let unit = U256::exp10((self.decimal_place - precision) as usize);
+                Self::new(self.atomics - self.atomics % unit, self.decimal_place)
+            }
+            _ => *self,
+        }
+    }
+
+    /// Whole units as `f64`, for gauges.
+    pub fn to_f64(&self) -> f64 {
+        // the decimal representation always parses as f64
+        self.to_string().parse().unwrap_or(f64::MAX)
+    }
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let digits = self.atomics.to_string();
+        let decimal_place = self.decimal_place as usize;
+        if decimal_place == 0 {
+            return f.write_str(&digits);
+        }
+        let digits = format!("{:0>width$}", digits, width = decimal_place + 1);
+        let (integer, fraction) = digits.split_at(digits.len() - decimal_place);
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            f.write_str(integer)
+        } else {
+            write!(f, "{}.{}", integer, fraction)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amount(atomics: &str, decimal_place: u32) -> Amount {
+        Amount::new(parse_atomics(atomics).unwrap(), decimal_place)
+    }
+
+    #[test]
+    fn display_keeps_fraction() {
+        assert_eq!(amount("400000000000000000", 18).to_string(), "0.4");
+        assert_eq!(amount("1", 6).to_string(), "0.000001");
+        assert_eq!(amount("1234500000", 6).to_string(), "1234.5");
+        assert_eq!(amount("200000000000", 6).to_string(), "200000");
+        assert_eq!(amount("0", 6).to_string(), "0");
+        assert_eq!(amount("42", 0).to_string(), "42");
+    }
+
+    #[test]
+    fn handles_amounts_beyond_u128() {
+        // 2^200 wei
+        let big = "1606938044258990275541962092341162602522202993782792835301376";
+        let amount = amount(big, 18);
+        assert_eq!(
+            amount.to_string(),
+            "1606938044258990275541962092341162602522202.993782792835301376"
+        );
+        assert!(amount.to_f64() > 1.6e42);
+    }
+
+    #[test]
+    fn precision_rounds_down() {
+        let amount = amount("987654321", 6);
+        assert_eq!(amount.with_precision(Some(2)).to_string(), "987.65");
+        assert_eq!(amount.with_precision(Some(0)).to_string(), "987");
+        assert_eq!(amount.with_precision(Some(9)).to_string(), "987.654321");
+        assert_eq!(amount.with_precision(None).to_string(), "987.654321");
+    }
+
+    #[test]
+    fn to_f64_keeps_fraction() {
+        assert_eq!(amount("900000", 6).to_f64(), 0.9);
+        assert_eq!(amount("400000000000000000", 18).to_f64(), 0.4);
+        assert_eq!(amount("200000000000", 6).to_f64(), 200000.0);
+    }
+
+    #[test]
+    fn parse_units_scales_by_decimal_place() {
+        assert_eq!(
+            parse_units("10", 18).unwrap(),
+            parse_atomics("10000000000000000000").unwrap()
+        );
+        assert_eq!(parse_units("0.5", 6).unwrap(), U256::from(500_000u64));
+        assert_eq!(parse_units("1.250000", 2).unwrap(), U256::from(125u64));
+        assert_eq!(parse_units("42", 0).unwrap(), U256::from(42u64));
+        assert!(parse_units("0.0000001", 6).is_err());
+        assert!(parse_units(".5", 6).is_err());
+        assert!(parse_units("1.2.3", 6).is_err());
+        assert!(parse_units("-1", 6).is_err());
+        assert!(parse_units("1e6", 6).is_err());
+    }
+
+    #[test]
+    fn parse_rejects_invalid_atomics() {
+        assert!(parse_atomics("sth100").is_err());
+        assert!(parse_atomics("-1").is_err());
+        assert!(parse_atomics("1.5").is_err());
+    }
+}
@@ -1,11 +1,20 @@
+use cosmos_balance_watcher::check::{
+    check_config, overall_status, query_balances, BalanceQuery, BalanceTable, CheckTable,
+    CONFIG_ERROR_EXIT_CODE,
+};
+use cosmos_balance_watcher::config::CoinType;
+use cosmos_balance_watcher::silence::{self, Silences};
+use cosmos_balance_watcher::source::SourceRegistry;
 use cosmos_balance_watcher::telemetry::{metrics_handler, register_custom_metrics};
-use cosmos_balance_watcher::{config, handle, DEFAULT_CONFIG_PATH};
+use cosmos_balance_watcher::{config, discover, handle, lint, DEFAULT_CONFIG_PATH};
 use env_logger::Builder;
 use log::{error, info, LevelFilter};
 use std::net::Ipv4Addr;
 use std::path::PathBuf;
 use std::result::Result;
 use std::str::FromStr;
+use std::sync::Arc;
+use std::time::Duration;
 use structopt::StructOpt;
 use warp::Filter;
 
@@ -20,6 +29,68 @@ enum BalanceWatcher {
     Start {
         #[structopt(short)]
         config_path: Option<PathBuf>,
+        /// Also reload the config when the file changes, checking at this interval (e.g. `10s`).
+        /// The config is always reloaded on SIGHUP.
+        #[structopt(long, parse(try_from_str = humantime::parse_duration))]
+        watch: Option<Duration>,
+    },
+    #[structopt(
+        name = "check",
+        about = "query every address once, print the balances and exit with 0 (ok), 1 (below min_balance), 2 (query failed) or 3 (config error)"
+    )]
+    Check {
+        #[structopt(short)]
+        config_path: Option<PathBuf>,
+    },
+    #[structopt(
+        name = "query",
+        about = "print the live balances of one address with the endpoints of a configured chain"
+    )]
+    Query {
+        #[structopt(short)]
+        config_path: Option<PathBuf>,
+        #[structopt(long)]
+        chain: String,
+        #[structopt(long)]
+        address: String,
+        /// Coin type to query; every bank balance is printed when unset.
+        #[structopt(long)]
+        coin_type: Option<String>,
+        /// Contract of a CW20 or EVM_ERC20 coin.
+        #[structopt(long)]
+        contract: Option<String>,
+        #[structopt(long)]
+        denom: Option<String>,
+        /// Defaults to the decimal place of a matching configured coin.
+        #[structopt(long)]
+        decimal_place: Option<u32>,
+    },
+    #[structopt(
+        name = "discover",
+        about = "print coin entries for every bank balance of an address, to paste into the config"
+    )]
+    Discover {
+        #[structopt(short)]
+        config_path: Option<PathBuf>,
+        #[structopt(long)]
+        chain: String,
+        #[structopt(long)]
+        address: String,
+        #[structopt(long, default_value = "discovered")]
+        role: String,
+    },
+    #[structopt(
+        name = "validate",
+        about = "report every problem of the config with its line number, exiting with 1 if there is any"
+    )]
+    Validate {
+        #[structopt(short)]
+        config_path: Option<PathBuf>,
+        /// Also try to connect to every gRPC and EVM endpoint.
+        #[structopt(long)]
+        check_endpoints: bool,
+        #[structopt(long, default_value = "5s", parse(try_from_str = humantime::parse_duration))]
+        timeout: Duration,
     },
 }
 
@@ -30,7 +101,38 @@ async fn main() {
 
     let opt = BalanceWatcher::from_args();
     let result = match opt {
-        BalanceWatcher::Start { config_path } => start(config_path).await,
+        BalanceWatcher::Start { config_path, watch } => start(config_path, watch).await,
+        BalanceWatcher::Check { config_path } => check(config_path).await,
+        BalanceWatcher::Query {
+            config_path,
+            chain,
+            address,
+            coin_type,
+            contract,
+            denom,
+            decimal_place,
+        } => {
+            let balance_query = BalanceQuery {
+                chain_id: chain,
+                address,
+                coin_type: coin_type.map(CoinType::from),
+                contract_address: contract,
+                denom,
+                decimal_place,
+            };
+            query(config_path, balance_query).await
+        }
+        BalanceWatcher::Discover {
+            config_path,
+            chain,
+            address,
+            role,
+        } => discover(config_path, &chain, &address, &role).await,
+        BalanceWatcher::Validate {
+            config_path,
+            check_endpoints,
+            timeout,
+        } => validate(config_path, check_endpoints.then_some(timeout)).await,
     };
     if let Err(e) = result {
         error!("{}", e);
@@ -38,7 +140,8 @@ async fn main() {
     }
 }
 
-async fn start(config_path: Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+/// The given config path, or chains.toml next to the executable.
+fn config_path(config_path: Option<PathBuf>) -> Result<PathBuf, Box<dyn std::error::Error>> {
     let default_path = format!(
         "{}/{}",
         std::env::current_exe()?.parent().unwrap().to_str().unwrap(),
@@ -49,22 +152,110 @@ async fn start(config_path: Option<PathBuf>) -> Result<(), Box<dyn std::error::E
     if !cp.exists() {
         Err("missing chains.toml file".into())
     } else {
-        let config = config::load(cp).expect("could not parse config");
+        Ok(cp)
+    }
+}
+
+async fn check(config_path_arg: Option<PathBuf>) -> Result<(), Box<dyn std::error::Error>> {
+    let config = match config_path(config_path_arg).and_then(|cp| Ok(config::load(cp)?)) {
+        Ok(config) => config,
+        Err(e) => {
+            error!("{}", e);
+            std::process::exit(CONFIG_ERROR_EXIT_CODE);
+        }
+    };
+    let checks = check_config(&config, &SourceRegistry::default()).await;
+    print!("{}", CheckTable(&checks));
+    let status = overall_status(&checks);
+    if status.exit_code() != 0 {
+        std::process::exit(status.exit_code());
+    }
+    Ok(())
+}
+
+async fn query(
+    config_path_arg: Option<PathBuf>,
+    query: BalanceQuery,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = config::load(config_path(config_path_arg)?)?;
+    let balances = query_balances(&config, &SourceRegistry::default(), &query).await?;
+    print!("{}", BalanceTable(&balances));
+    Ok(())
+}
 
-        register_custom_metrics();
-        let metrics_route = warp::path!("metrics").and_then(metrics_handler);
-        tokio::task::spawn(handle::account_status_collector(config.clone()));
+async fn discover(
+    config_path_arg: Option<PathBuf>,
+    chain_id: &str,
+    address: &str,
+    role: &str,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = config::load(config_path(config_path_arg)?)?;
+    let discovered = discover::discover(&config, chain_id, address, role).await?;
+    discovered.store_writer(std::io::stdout())?;
+    Ok(())
+}
 
+async fn validate(
+    config_path_arg: Option<PathBuf>,
+    endpoint_timeout: Option<Duration>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let cp = config_path(config_path_arg)?;
+    let config_toml = std::fs::read_to_string(&cp)?;
+    let diagnostics = match lint::lint(&config_toml) {
+        Ok(config) => match endpoint_timeout {
+            Some(timeout) => {
+                lint::locate(&config_toml, &lint::check_endpoints(&config, timeout).await)
+            }
+            None => Vec::new(),
+        },
+        Err(diagnostics) => diagnostics,
+    };
+    if diagnostics.is_empty() {
+        println!("{}: ok", cp.display());
+        return Ok(());
+    }
+    for diagnostic in diagnostics.iter() {
+        println!("{}: {}", cp.display(), diagnostic);
+    }
+    std::process::exit(1);
+}
+
+async fn start(
+    config_path_arg: Option<PathBuf>,
+    watch: Option<Duration>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let cp = config_path(config_path_arg)?;
+    let config = config::load(&cp)?;
+
+    register_custom_metrics();
+    let metrics_route = warp::path!("metrics").and_then(metrics_handler);
+    let silences = Arc::new(Silences::default());
+    tokio::task::spawn(handle::account_status_collector(
+        cp,
+        config.clone(),
+        Arc::new(SourceRegistry::default()),
+        silences.clone(),
+        watch,
+    ));
+
+    if let Some(api) = &config.api {
+        let addr = (Ipv4Addr::from_str(&api.host)?, api.port);
         info!(
-            "Started prometheus metrics server: http://{}:{}/metrics",
-            &config.prometheus.host, &config.prometheus.port
+            "Started silence API server: http://{}:{}/api/silences",
+            &api.host, &api.port
         );
-        warp::serve(metrics_route)
-            .run((
-                Ipv4Addr::from_str(&config.prometheus.host)?,
-                config.prometheus.port as u16,
-            ))
-            .await;
-        Ok(())
+        tokio::task::spawn(warp::serve(silence::routes(silences)).run(addr));
     }
+
+    info!(
+        "Started prometheus metrics server: http://{}:{}/metrics",
+        &config.prometheus.host, &config.prometheus.port
+    );
+    warp::serve(metrics_route)
+        .run((
+            Ipv4Addr::from_str(&config.prometheus.host)?,
+            config.prometheus.port as u16,
+        ))
+        .await;
+    Ok(())
 }
@@ -0,0 +1,517 @@
+//! One-shot balance checks and queries
+use crate::amount::Amount;
+use crate::config::{self, CoinType, Config};
+use crate::error::Error;
+use crate::handle::{coin_groups, BalanceStatus, CoinEntity};
+use crate::query::get_cosmos_balances;
+use crate::source::{complete_balances, missing_balance, ChainEndpoints, SourceRegistry};
+use futures::future::join_all;
+use std::fmt;
+use std::time::Duration;
+use web3::types::U256;
+
+/// Outcome of checking one coin, ordered by severity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckStatus {
+    Balance(BalanceStatus),
+    AboveMaximum,
+    QueryFailed,
+}
+
+impl CheckStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CheckStatus::Balance(status) => status.as_str(),
+            CheckStatus::AboveMaximum => "over",
+            CheckStatus::QueryFailed => "failed",
+        }
+    }
+
+    /// 0 when every balance is above `min_balance` and within `max_balance`,
+    /// 1 when one is not and 2 when one could not be queried. The config
+    /// failing to load is [`CONFIG_ERROR_EXIT_CODE`].
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CheckStatus::Balance(BalanceStatus::Ok) => 0,
+            CheckStatus::Balance(_) | CheckStatus::AboveMaximum => 1,
+            CheckStatus::QueryFailed => 2,
+        }
+    }
+}
+
+/// Exit code of `check` when the config cannot be loaded, apart from those
+/// of [`CheckStatus`] so a broken config is not taken for a low balance.
+pub const CONFIG_ERROR_EXIT_CODE: i32 = 3;
+
+/// Balance of one configured coin, or why it could not be queried.
+pub struct CoinCheck {
+    pub chain_id: String,
+    pub role: String,
+    pub address: String,
+    pub coin: CoinEntity,
+    pub balance: Result<U256, String>,
+}
+
+impl CoinCheck {
+    pub fn status(&self) -> CheckStatus {
+        match self.balance {
+            Ok(amount) => match self.coin.status(amount) {
+                BalanceStatus::Ok if self.coin.above_max(amount) == Some(true) => {
+                    CheckStatus::AboveMaximum
+                }
+                status => CheckStatus::Balance(status),
+            },
+            Err(_) => CheckStatus::QueryFailed,
+        }
+    }
+}
+
+/// The worst status of `checks`.
+pub fn overall_status(checks: &[CoinCheck]) -> CheckStatus {
+    checks
+        .iter()
+        .map(CoinCheck::status)
+        .max()
+        .unwrap_or(CheckStatus::Balance(BalanceStatus::Ok))
+}
+
+/// Query every coin of every configured address once, concurrently. The
+/// checks are returned in config order.
+pub async fn check_config(config: &Config, registry: &SourceRegistry) -> Vec<CoinCheck> {
+    let mut accounts = Vec::new();
+    for chain_config in config.chains.iter() {
+        let endpoints = ChainEndpoints::from_config(chain_config);
+        for chain_address in chain_config.addresses.iter() {
+            let endpoints = endpoints.clone();
+            accounts.push(async move {
+                let mut checks = Vec::new();
+                for (coin_type, coin_entities) in coin_groups(chain_address) {
+                    let balances = match (
+                        chain_address
+                            .query_address(&coin_type, chain_config.bech32_prefix.as_deref()),
+                        registry.get(&coin_type),
+                    ) {
+                        (Err(e), _) => vec![Err(e); coin_entities.len()],
+                        (_, None) => vec![
+                            Err(format!(
+                                "no balance source registered for coin type {}",
+                                coin_type
+                            ));
+                            coin_entities.len()
+                        ],
+                        (Ok(query_address), Some(source)) => complete_balances(
+                            &coin_entities,
+                            source
+                                .get_balances(query_address, &coin_entities, &endpoints)
+                                .await,
+                        )
+                        .into_iter()
+                        .map(|result| {
+                            result
+                                .map(|(balance, _)| balance.amount)
+                                .map_err(|e| e.to_string())
+                        })
+                        .collect(),
+                    };
+                    for (coin, balance) in coin_entities.into_iter().zip(balances) {
+                        checks.push(CoinCheck {
+                            chain_id: chain_config.id.clone(),
+                            role: chain_address.role.clone(),
+                            address: chain_address.address.clone(),
+                            coin,
+                            balance,
+                        });
+                    }
+                }
+                checks
+            });
+        }
+    }
+    join_all(accounts).await.into_iter().flatten().collect()
+}
+
+/// Renders checks as a plain text table followed by the query errors.
+pub struct CheckTable<'a>(pub &'a [CoinCheck]);
+
+impl fmt::Display for CheckTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = [
+            "CHAIN",
+            "ROLE",
+            "ADDRESS",
+            "DENOM",
+            "BALANCE",
+            "MIN_BALANCE",
+            "CRITICAL_BALANCE",
+            "MAX_BALANCE",
+            "STATUS",
+        ]
+        .map(String::from);
+        let rows = self
+            .0
+            .iter()
+            .map(|check| {
+                let balance = match &check.balance {
+                    Ok(amount) => Amount::new(*amount, check.coin.decimal_place)
+                        .with_precision(check.coin.display_precision)
+                        .to_string(),
+                    Err(_) => "-".to_string(),
+                };
+                [
+                    check.chain_id.clone(),
+                    check.role.clone(),
+                    check.address.clone(),
+                    check.coin.display_denom.clone(),
+                    balance,
+                    check.coin.display_min_balance.clone(),
+                    check.coin.display_critical_balance.clone(),
+                    check.coin.display_max_balance.clone(),
+                    check.status().as_str().to_string(),
+                ]
+            })
+            .collect::<Vec<_>>();
+        write_table(f, &header, &rows)?;
+        for check in self.0.iter() {
+            if let Err(e) = &check.balance {
+                writeln!(
+                    f,
+                    "\n{} {} ({}) {}: {}",
+                    check.chain_id, check.role, check.address, check.coin.display_denom, e
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Writes `rows` under `header` with left aligned columns.
+pub(crate) fn write_table<const N: usize>(
+    f: &mut fmt::Formatter<'_>,
+    header: &[String; N],
+    rows: &[[String; N]],
+) -> fmt::Result {
+    let mut widths = header.clone().map(|title| title.len());
+    for row in rows.iter() {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.len());
+        }
+    }
+    for row in std::iter::once(header).chain(rows.iter()) {
+        let line = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<_>>()
+            .join("  ");
+        writeln!(f, "{}", line.trim_end())?;
+    }
+    Ok(())
+}
+
+/// A balance query for an address that need not be in the config.
+#[derive(Clone, Debug, Default)]
+pub struct BalanceQuery {
+    pub chain_id: String,
+    pub address: String,
+    /// Every bank balance of the address when unset.
+    pub coin_type: Option<CoinType>,
+    pub contract_address: Option<String>,
+    pub denom: Option<String>,
+    pub decimal_place: Option<u32>,
+}
+
+/// A live balance with the endpoint that reported it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct QueriedBalance {
+    pub denom: String,
+    pub amount: Amount,
+    pub endpoint: String,
+}
+
+/// Query the live balances of `query` with the endpoints of its chain in
+/// `config`. The decimal place is the one of the query, else the one of a
+/// configured coin with the same coin type, denom and contract, else the
+/// default.
+pub async fn query_balances(
+    config: &Config,
+    registry: &SourceRegistry,
+    query: &BalanceQuery,
+) -> Result<Vec<QueriedBalance>, Error> {
+    let chain_config = config
+        .chains
+        .iter()
+        .find(|chain_config| chain_config.id == query.chain_id)
+        .ok_or_else(|| Error::unknown_chain(query.chain_id.clone()))?;
+    let endpoints = ChainEndpoints::from_config(chain_config);
+    let decimal_place = |coin_type: &CoinType, denom: &str| {
+        query.decimal_place.unwrap_or_else(|| {
+            chain_config
+                .addresses
+                .iter()
+                .flat_map(|chain_address| chain_address.coins.iter())
+                .find(|coin| {
+                    &coin.coin_type == coin_type
+                        && coin.denom == denom
+                        && (query.contract_address.is_none()
+                            || coin.contract_address == query.contract_address)
+                })
+                .map(|coin| coin.decimal_place)
+                .unwrap_or_else(config::default::decimal_place)
+        })
+    };
+    let chain_address = config::Address {
+        address: query.address.clone(),
+        hex_address: None,
+        role: String::new(),
+        disable_balance: None,
+        balance_url: None,
+        refresh: config::default::refresh(),
+        spend_window: config::default::spend_window(),
+        coins: Vec::new(),
+    };
+    let query_address = |coin_type: &CoinType| {
+        chain_address
+            .query_address(coin_type, chain_config.bech32_prefix.as_deref())
+            .map_err(Error::invalid_address)
+    };
+
+    let Some(coin_type) = &query.coin_type else {
+        let address = query_address(&CoinType::COSMOS)?;
+        let (balances, endpoint) = endpoints
+            .grpc
+            .execute(&CoinType::COSMOS, "*", |grpc_addr| {
+                get_cosmos_balances(address.clone(), grpc_addr)
+            })
+            .await?;
+        return Ok(balances
+            .into_iter()
+            .map(|balance| QueriedBalance {
+                amount: Amount::new(
+                    balance.amount,
+                    decimal_place(&CoinType::COSMOS, &balance.denom),
+                ),
+                denom: balance.denom,
+                endpoint: endpoint.clone(),
+            })
+            .collect());
+    };
+
+    let source = registry
+        .get(coin_type)
+        .ok_or_else(|| Error::unknown_coin_type(coin_type.to_string()))?;
+    let denom = query
+        .denom
+        .clone()
+        .or_else(|| query.contract_address.clone())
+        .unwrap_or_else(|| match coin_type {
+            CoinType::EVM => "wei".to_string(),
+            _ => coin_type.to_string(),
+        });
+    let coin_entity = CoinEntity {
+        coin_type: coin_type.clone(),
+        contract_address: query.contract_address.clone(),
+        decimal_place: decimal_place(coin_type, &denom),
+        display_precision: None,
+        display_denom: denom.clone(),
+        denom,
+        display_min_balance: "0".to_string(),
+        min_balance: U256::zero(),
+        display_critical_balance: String::new(),
+        critical_balance: None,
+        display_max_balance: String::new(),
+        max_balance: None,
+        clear_balance: U256::zero(),
+        for_duration: Duration::ZERO,
+    };
+    let results = source
+        .get_balances(
+            query_address(coin_type)?,
+            std::slice::from_ref(&coin_entity),
+            &endpoints,
+        )
+        .await;
+    let (balance, endpoint) = results
+        .into_iter()
+        .next()
+        .unwrap_or_else(|| Err(missing_balance(&coin_entity)))?;
+    Ok(vec![QueriedBalance {
+        denom: coin_entity.denom,
+        amount: Amount::new(balance.amount, coin_entity.decimal_place),
+        endpoint,
+    }])
+}
+
+/// Renders queried balances as a plain text table.
+pub struct BalanceTable<'a>(pub &'a [QueriedBalance]);
+
+impl fmt::Display for BalanceTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = ["DENOM", "BALANCE", "ATOMICS", "ENDPOINT"].map(String::from);
+        let rows = self
+            .0
+            .iter()
+            .map(|balance| {
+                [
+                    balance.denom.clone(),
+                    balance.amount.to_string(),
+                    balance.amount.atomics().to_string(),
+                    balance.endpoint.clone(),
+                ]
+            })
+            .collect::<Vec<_>>();
+        write_table(f, &header, &rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::amount::Balance;
+    use crate::source::{BalanceSource, CoinBalance};
+    use async_trait::async_trait;
+
+    /// Reports 1000 atomics of every denom but `ufail`.
+    struct FixedSource;
+
+    #[async_trait]
+    impl BalanceSource for FixedSource {
+        async fn get_balances(
+            &self,
+            _address: String,
+            coin_entities: &[CoinEntity],
+            _endpoints: &ChainEndpoints,
+        ) -> Vec<CoinBalance> {
+            coin_entities
+                .iter()
+                .map(|coin_entity| {
+                    if coin_entity.denom == "ufail" {
+                        return Err(Error::amount_parse("ufail".to_string()));
+                    }
+                    Ok((
+                        Balance {
+                            denom: coin_entity.denom.clone(),
+                            amount: U256::from(1000),
+                        },
+                        "fixed".to_string(),
+                    ))
+                })
+                .collect()
+        }
+    }
+
+    /// Returns no balance at all.
+    struct EmptySource;
+
+    #[async_trait]
+    impl BalanceSource for EmptySource {
+        async fn get_balances(
+            &self,
+            _address: String,
+            _coin_entities: &[CoinEntity],
+            _endpoints: &ChainEndpoints,
+        ) -> Vec<CoinBalance> {
+            Vec::new()
+        }
+    }
+
+    fn config(denoms: &[(&str, &str)]) -> Config {
+        let coins = denoms
+            .iter()
+            .map(|(denom, min_balance)| {
+                format!(
+                    "[[chains.addresses.coins]]\ncoin_type = 'FIXED'\ndenom = '{}'\nmin_balance = '{}'\n",
+                    denom, min_balance
+                )
+            })
+            .collect::<String>();
+        toml::from_str(&format!(
+            "[[chains]]\nid = 'chain_A'\n[[chains.addresses]]\naddress = 'someone'\nrole = 'relayer'\n{}",
+            coins
+        ))
+        .unwrap()
+    }
+
+    async fn check(denoms: &[(&str, &str)]) -> Vec<CoinCheck> {
+        let mut registry = SourceRegistry::empty();
+        registry.register(CoinType::from("FIXED".to_string()), FixedSource);
+        check_config(&config(denoms), &registry).await
+    }
+
+    #[actix_rt::test]
+    async fn exit_code_follows_worst_status() {
+        let checks = check(&[("uom", "10")]).await;
+        assert_eq!(overall_status(&checks).exit_code(), 0);
+
+        let checks = check(&[("uom", "10"), ("uusdc", "1000")]).await;
+        assert_eq!(
+            checks[1].status(),
+            CheckStatus::Balance(BalanceStatus::Warning)
+        );
+        assert_eq!(overall_status(&checks).exit_code(), 1);
+
+        let mut config = config(&[("uom", "10")]);
+        config.chains[0].addresses[0].coins[0].max_balance = Some("999".to_string());
+        let mut registry = SourceRegistry::empty();
+        registry.register(CoinType::from("FIXED".to_string()), FixedSource);
+        let checks = check_config(&config, &registry).await;
+        assert_eq!(checks[0].status(), CheckStatus::AboveMaximum);
+        assert_eq!(overall_status(&checks).exit_code(), 1);
+
+        let checks = check(&[("ufail", "10"), ("uusdc", "1000")]).await;
+        assert_eq!(overall_status(&checks).exit_code(), 2);
+
+        // a coin the source returned nothing for is not left out
+        let mut registry = SourceRegistry::empty();
+        registry.register(CoinType::from("FIXED".to_string()), EmptySource);
+        let checks = check_config(&config, &registry).await;
+        assert_eq!(checks.len(), 1);
+        assert_eq!(overall_status(&checks).exit_code(), 2);
+    }
+
+    #[actix_rt::test]
+    async fn table_lists_every_coin() {
+        let checks = check(&[("uom", "10"), ("ufail", "10")]).await;
+        let table = CheckTable(&checks).to_string();
+        let lines = table.lines().collect::<Vec<_>>();
+        assert!(lines[0].starts_with("CHAIN"));
+        assert!(lines[1].contains("uom") && lines[1].contains("0.001") && lines[1].ends_with("ok"));
+        assert!(lines[2].contains("ufail") && lines[2].ends_with("failed"));
+        assert!(table.contains("chain_A relayer (someone) ufail:"));
+    }
+
+    #[actix_rt::test]
+    async fn query_uses_configured_decimal_place() {
+        let mut registry = SourceRegistry::empty();
+        registry.register(CoinType::from("FIXED".to_string()), FixedSource);
+        let mut config = config(&[("uom", "10")]);
+        config.chains[0].addresses[0].coins[0].decimal_place = 2;
+        let mut query = BalanceQuery {
+            chain_id: "chain_A".to_string(),
+            address: "anyone".to_string(),
+            coin_type: Some(CoinType::from("FIXED".to_string())),
+            denom: Some("uom".to_string()),
+            ..Default::default()
+        };
+
+        let balances = query_balances(&config, &registry, &query).await.unwrap();
+        assert_eq!(balances[0].amount.to_string(), "10");
+        assert_eq!(balances[0].endpoint, "fixed");
+
+        query.decimal_place = Some(3);
+        let balances = query_balances(&config, &registry, &query).await.unwrap();
+        assert_eq!(
+            BalanceTable(&balances).to_string().lines().nth(1).unwrap(),
+            "uom    1        1000     fixed"
+        );
+
+        query.chain_id = "chain_B".to_string();
+        assert!(query_balances(&config, &registry, &query).await.is_err());
+
+        registry.register(CoinType::from("FIXED".to_string()), EmptySource);
+        query.chain_id = "chain_A".to_string();
+        let err = query_balances(&config, &registry, &query)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("returned no result"), "{}", err);
+    }
+}
@@ -1,10 +1,23 @@
 //! Chain configuration
 use serde_derive::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::{fs, fs::File, io::Write, path::Path, time::Duration};
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, SystemTime};
+use std::{
+    fs,
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
 use tendermint_rpc::Url;
+use web3::types::U256;
 
+use crate::address::{bech32_to_hex, check_bech32, check_eip55, hex_to_bech32, is_hex_address};
+use crate::amount::{parse_atomics, parse_units};
+use crate::email::parse_time_of_day;
 use crate::error::Error;
+use crate::notify::check_template;
+use crate::silence::check_silence;
+use lettre::message::Mailbox;
 
 pub mod default {
     use super::*;
@@ -13,6 +26,10 @@ pub mod default {
         Duration::from_secs(120)
     }
 
+    pub fn query_timeout() -> Duration {
+        Duration::from_secs(10)
+    }
+
     pub fn coin_type() -> CoinType {
         CoinType::COSMOS
     }
@@ -20,15 +37,62 @@ pub mod default {
     pub fn decimal_place() -> u32 {
         6
     }
+
+    pub fn expire_after_refreshes() -> u32 {
+        3
+    }
+
+    pub fn spend_window() -> Duration {
+        Duration::from_secs(6 * 3600)
+    }
+
+    pub fn retries() -> u32 {
+        3
+    }
+
+    pub fn retry_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    pub fn notify_timeout() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    pub fn send_resolved() -> bool {
+        true
+    }
+
+    pub fn resend_interval() -> Duration {
+        Duration::from_secs(60)
+    }
+
+    pub fn smtp_port() -> u16 {
+        587
+    }
+
+    pub fn api_host() -> String {
+        "127.0.0.1".to_string()
+    }
+
+    pub fn api_port() -> u16 {
+        9091
+    }
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct Config {
-    #[serde(default)]
+    #[serde(default, skip_serializing_if = "PrometheusConfig::is_default")]
     pub prometheus: PrometheusConfig,
     #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
     pub chains: Vec<ChainConfig>,
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    pub webhooks: Vec<WebhookConfig>,
+    pub alertmanager: Option<AlertmanagerConfig>,
+    pub smtp: Option<SmtpConfig>,
+    pub api: Option<ApiConfig>,
+    #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
+    pub silences: Vec<SilenceConfig>,
 }
 
 impl Config {
@@ -37,7 +101,7 @@ impl Config {
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct PrometheusConfig {
     pub host: String,
@@ -45,6 +109,25 @@ pub struct PrometheusConfig {
     #[serde(default)]
     #[serde(with = "humantime_serde")]
     pub reset: Option<Duration>,
+    /// Gauge series of an address not updated for this many of its `refresh`
+    /// intervals are removed; 0 keeps them until the next `reset`.
+    #[serde(default = "default::expire_after_refreshes")]
+    pub expire_after_refreshes: u32,
+}
+
+impl PrometheusConfig {
+    fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+
+    /// How long the gauge series of an address refreshed every `refresh` live
+    /// without being updated.
+    pub fn series_ttl(&self, refresh: Duration) -> Option<Duration> {
+        match self.expire_after_refreshes {
+            0 => None,
+            refreshes => Some(refresh * refreshes),
+        }
+    }
 }
 
 impl Default for PrometheusConfig {
@@ -53,49 +136,380 @@ impl Default for PrometheusConfig {
             host: "0.0.0.0".to_string(),
             port: 9090,
             reset: None,
+            expire_after_refreshes: default::expire_after_refreshes(),
+        }
+    }
+}
+
+/// A URL alerts are posted to when a coin crosses its min_balance.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct WebhookConfig {
+    pub name: String,
+    pub url: String,
+    /// JSON body with `{{field}}` placeholders, the alert as a JSON object when unset.
+    pub template: Option<String>,
+    /// Attempts after the first failed one.
+    #[serde(default = "default::retries")]
+    pub retries: u32,
+    #[serde(default = "default::retry_interval", with = "humantime_serde")]
+    pub retry_interval: Duration,
+    #[serde(default = "default::notify_timeout", with = "humantime_serde")]
+    pub timeout: Duration,
+    /// Also post when the balance is back above min_balance.
+    #[serde(default = "default::send_resolved")]
+    pub send_resolved: bool,
+}
+
+/// An Alertmanager alerts are pushed to while a coin is at or below its
+/// min_balance.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct AlertmanagerConfig {
+    /// Base URL, alerts are posted to `{url}/api/v2/alerts`.
+    pub url: String,
+    /// How often a firing alert is posted again, so Alertmanager keeps it active.
+    #[serde(default = "default::resend_interval", with = "humantime_serde")]
+    pub resend_interval: Duration,
+    /// Labels added to every alert, e.g. `env = 'prod'`.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub labels: BTreeMap<String, String>,
+    /// Attempts after the first failed one.
+    #[serde(default = "default::retries")]
+    pub retries: u32,
+    #[serde(default = "default::retry_interval", with = "humantime_serde")]
+    pub retry_interval: Duration,
+    #[serde(default = "default::notify_timeout", with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+/// The listener of the HTTP API managing silences, apart from the metrics
+/// one as it can mute every notification.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ApiConfig {
+    #[serde(default = "default::api_host")]
+    pub host: String,
+    #[serde(default = "default::api_port")]
+    pub port: u16,
+}
+
+/// A mail server alerts and a daily digest of every balance are sent through.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SmtpConfig {
+    pub host: String,
+    #[serde(default = "default::smtp_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    pub username: Option<String>,
+    /// Better read from `password_env` or `password_file` than kept in the
+    /// config, never serialized.
+    #[serde(default, skip_serializing)]
+    pub password: Option<String>,
+    /// Environment variable holding the password.
+    pub password_env: Option<String>,
+    /// File holding the password, without its trailing newline.
+    pub password_file: Option<PathBuf>,
+    /// Sender mailbox, e.g. `Balance Watcher <watcher@example.com>`.
+    pub from: String,
+    pub to: Vec<String>,
+    /// Also mail when an alert resolves.
+    #[serde(default = "default::send_resolved")]
+    pub send_resolved: bool,
+    /// UTC time of day (`HH:MM`) the digest of every balance is sent at, no
+    /// digest when unset.
+    pub digest_at: Option<String>,
+    /// Attempts after the first failed one.
+    #[serde(default = "default::retries")]
+    pub retries: u32,
+    #[serde(default = "default::retry_interval", with = "humantime_serde")]
+    pub retry_interval: Duration,
+    #[serde(default = "default::notify_timeout", with = "humantime_serde")]
+    pub timeout: Duration,
+}
+
+impl SmtpConfig {
+    /// The password given, or read from `password_env` or `password_file`.
+    pub fn password(&self) -> Result<Option<String>, String> {
+        if let Some(password) = &self.password {
+            return Ok(Some(password.clone()));
         }
+        if let Some(name) = &self.password_env {
+            return std::env::var(name)
+                .map(Some)
+                .map_err(|e| format!("password_env '{}': {}", name, e));
+        }
+        if let Some(path) = &self.password_file {
+            return fs::read_to_string(path)
+                .map(|password| Some(password.trim_end_matches(['\r', '\n']).to_string()))
+                .map_err(|e| format!("password_file '{}': {}", path.display(), e));
+        }
+        Ok(None)
+    }
+}
+
+/// How the connection to the mail server is secured.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    /// Upgrade a plain connection with STARTTLS, usually on port 587.
+    #[default]
+    Starttls,
+    /// TLS from the start, usually on port 465.
+    Tls,
+    /// No encryption, e.g. for a local relay.
+    None,
+}
+
+/// Suppresses the notifications of the coins matched by every matcher set,
+/// e.g. while a wallet is drained on purpose. Unset matchers match any value.
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct SilenceConfig {
+    pub chain_id: Option<String>,
+    pub address: Option<String>,
+    pub role: Option<String>,
+    /// The display denom of the coin, matched ignoring case.
+    pub denom: Option<String>,
+    /// RFC 3339 time the silence starts at, right away when unset.
+    #[serde(default, with = "humantime_serde")]
+    pub starts_at: Option<SystemTime>,
+    #[serde(with = "humantime_serde")]
+    pub ends_at: SystemTime,
+    pub comment: Option<String>,
+}
+
+impl SilenceConfig {
+    pub fn is_active(&self, now: SystemTime) -> bool {
+        self.starts_at.is_none_or(|starts_at| starts_at <= now) && now < self.ends_at
+    }
+
+    pub fn matches(&self, chain_id: &str, address: &str, role: &str, denom: &str) -> bool {
+        self.chain_id
+            .as_ref()
+            .is_none_or(|matcher| matcher == chain_id)
+            && self
+                .address
+                .as_ref()
+                .is_none_or(|matcher| matcher == address)
+            && self.role.as_ref().is_none_or(|matcher| matcher == role)
+            && self
+                .denom
+                .as_ref()
+                .is_none_or(|matcher| matcher.eq_ignore_ascii_case(denom))
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct ChainConfig {
     pub id: String,
-    pub grpc_addr: Option<Url>,
-    pub evm_addr: Option<Url>,
+    /// Human readable part expected on bech32 addresses and contracts of the chain.
+    pub bech32_prefix: Option<String>,
+    /// One gRPC endpoint or a list of them, tried in `endpoint_selection` order.
+    #[serde(
+        default = "Vec::new",
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub grpc_addr: Vec<Url>,
+    /// One EVM JSON-RPC endpoint or a list of them, tried in `endpoint_selection` order.
+    #[serde(
+        default = "Vec::new",
+        deserialize_with = "one_or_many",
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub evm_addr: Vec<Url>,
+    #[serde(default)]
+    pub endpoint_selection: EndpointSelection,
+    /// How long one endpoint is given to answer a query before failing over
+    /// to the next one.
+    #[serde(default = "default::query_timeout", with = "humantime_serde")]
+    pub query_timeout: Duration,
     #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
     pub addresses: Vec<Address>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+/// How the next endpoint is picked when a chain has several of them.
+#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EndpointSelection {
+    /// Always start from the first endpoint and fail over down the list.
+    #[default]
+    Priority,
+    /// Rotate the starting endpoint on every query.
+    RoundRobin,
+}
+
+/// Accept either a single value or a list of values.
+fn one_or_many<'de, D, T>(deserializer: D) -> Result<Vec<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: serde::Deserialize<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany<T> {
+        One(T),
+        Many(Vec<T>),
+    }
+
+    Ok(
+        match <OneOrMany<T> as serde::Deserialize>::deserialize(deserializer)? {
+            OneOrMany::One(value) => vec![value],
+            OneOrMany::Many(values) => values,
+        },
+    )
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct Address {
+    /// Bech32 or 0x address; the other form is derived when a coin needs it.
     pub address: String,
+    /// 0x address to use for EVM coins instead of the one derived from `address`.
     pub hex_address: Option<String>,
     pub role: String,
     pub disable_balance: Option<bool>,
     pub balance_url: Option<String>,
     #[serde(default = "default::refresh", with = "humantime_serde")]
     pub refresh: Duration,
+    /// How far back balances are kept to estimate the outflow rate.
+    #[serde(default = "default::spend_window", with = "humantime_serde")]
+    pub spend_window: Duration,
     #[serde(default = "Vec::new", skip_serializing_if = "Vec::is_empty")]
     pub coins: Vec<Coin>,
 }
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
+impl Address {
+    /// The `address` label of its metrics and alerts: `hex_address` when set,
+    /// as before the other form was derived, `address` otherwise.
+    pub fn label(&self) -> &str {
+        self.hex_address.as_deref().unwrap_or(&self.address)
+    }
+
+    /// The form of the address queried for `coin_type`: bech32 for COSMOS and
+    /// CW20 coins, 0x for EVM and EVM_ERC20 coins, as configured otherwise.
+    /// On ethsecp256k1 chains both forms share the same 20 bytes, so the
+    /// missing one is derived from `address`.
+    pub fn query_address(
+        &self,
+        coin_type: &CoinType,
+        bech32_prefix: Option<&str>,
+    ) -> Result<String, String> {
+        match coin_type {
+            CoinType::COSMOS | CoinType::CW20 => {
+                if !is_hex_address(&self.address) {
+                    return Ok(self.address.clone());
+                }
+                let prefix = bech32_prefix.ok_or_else(|| {
+                    format!(
+                        "coin_type {} needs a bech32 address, set bech32_prefix on the chain to derive it",
+                        coin_type
+                    )
+                })?;
+                hex_to_bech32(&self.address, prefix)
+            }
+            CoinType::EVM | CoinType::EVM_ERC20 => match &self.hex_address {
+                Some(hex_address) => Ok(hex_address.clone()),
+                None if is_hex_address(&self.address) => Ok(self.address.clone()),
+                None => bech32_to_hex(&self.address),
+            },
+            CoinType::Custom(_) => Ok(self.address.clone()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct Coin {
+    /// Warning threshold, also accepted as `warning_balance`. See
+    /// [`Coin::parse_threshold`] for the accepted forms.
+    #[serde(alias = "warning_balance")]
     pub min_balance: String,
+    /// Lower threshold at which `account_status` turns critical.
+    pub critical_balance: Option<String>,
+    /// Ceiling above which `account_ceiling_status` turns 1.
+    pub max_balance: Option<String>,
+    /// Balance a firing alert must rise above to resolve, min_balance when unset.
+    pub clear_balance: Option<String>,
+    /// How long the balance must stay at or below min_balance before the
+    /// alert fires, checked on every refresh.
+    #[serde(rename = "for", default, with = "humantime_serde")]
+    pub for_duration: Option<Duration>,
     pub denom: String,
     pub display_denom: Option<String>,
     pub contract_address: Option<String>,
     #[serde(default = "default::decimal_place")]
     pub decimal_place: u32, // default 6
+    /// Fractional digits kept in `account_balance` and the `min_balance` label,
+    /// rounding down. All `decimal_place` digits are kept when unset.
+    pub display_precision: Option<u32>,
     #[serde(default = "default::coin_type")]
     pub coin_type: CoinType,
 }
 
+impl Coin {
+    /// The denom thresholds are written in when given in whole units.
+    pub fn display_denom(&self) -> &str {
+        self.display_denom.as_deref().unwrap_or(&self.denom)
+    }
+
+    /// A threshold in atomic units. A bare integer, or an integer followed by
+    /// the denom itself (e.g. `'500000 uom'`), is taken as atomic units. An
+    /// amount followed by a `display_denom` other than the denom (e.g.
+    /// `'10 ETH'` or `'0.5 OM'`) is scaled by `decimal_place`.
+    pub fn parse_threshold(&self, value: &str) -> Result<U256, String> {
+        let value = value.trim();
+        let (amount, unit) = match value.find(|c: char| !c.is_ascii_digit() && c != '.') {
+            Some(index) => (value[..index].trim_end(), value[index..].trim_start()),
+            None => (value, ""),
+        };
+        let display_denom = self
+            .display_denom
+            .as_deref()
+            .filter(|display_denom| *display_denom != self.denom);
+        let result = if amount.is_empty() {
+            return Err(format!("'{}' is not an amount", value));
+        } else if unit.is_empty() || unit == self.denom {
+            if amount.contains('.') {
+                return Err(match display_denom {
+                    Some(display_denom) => format!(
+                        "'{}' needs a unit, e.g. '{} {}'",
+                        value, amount, display_denom
+                    ),
+                    None => format!(
+                        "'{}' is not a whole number of {}, set display_denom to give it in whole units",
+                        value, self.denom
+                    ),
+                });
+            }
+            parse_atomics(amount)
+        } else if display_denom
+            .is_some_and(|display_denom| unit.eq_ignore_ascii_case(display_denom))
+        {
+            parse_units(amount, self.decimal_place)
+        } else {
+            return Err(match display_denom {
+                Some(display_denom) => format!(
+                    "'{}' has unit '{}', neither the display denom '{}' nor the denom '{}'",
+                    value, unit, display_denom, self.denom
+                ),
+                None => format!(
+                    "'{}' has unit '{}', not the denom '{}'",
+                    value, unit, self.denom
+                ),
+            });
+        };
+        result.map_err(|e| format!("'{}' is not a valid amount: {}", value, e))
+    }
+}
+
 #[allow(non_camel_case_types)]
 #[derive(Clone, Debug, Deserialize, Serialize, PartialEq, Eq, Hash)]
+#[serde(from = "String", into = "String")]
 pub enum CoinType {
     COSMOS,
     CW20,
@@ -103,60 +517,420 @@ pub enum CoinType {
     /// EVM ERC-20 token balance via eth_call → balanceOf(address).
     /// Requires `contract_address` to be set on the coin.
     EVM_ERC20,
+    /// Any other coin type, served by a source registered downstream in
+    /// [`crate::source::SourceRegistry`].
+    Custom(String),
+}
+
+impl CoinType {
+    /// The coin types served by the sources of a default
+    /// [`crate::source::SourceRegistry`].
+    pub const BUILTIN: [CoinType; 4] = [
+        CoinType::COSMOS,
+        CoinType::CW20,
+        CoinType::EVM,
+        CoinType::EVM_ERC20,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            CoinType::COSMOS => "COSMOS",
+            CoinType::CW20 => "CW20",
+            CoinType::EVM => "EVM",
+            CoinType::EVM_ERC20 => "EVM_ERC20",
+            CoinType::Custom(name) => name,
+        }
+    }
+}
+
+impl From<String> for CoinType {
+    fn from(name: String) -> Self {
+        match name.as_str() {
+            "COSMOS" => CoinType::COSMOS,
+            "CW20" => CoinType::CW20,
+            "EVM" => CoinType::EVM,
+            "EVM_ERC20" => CoinType::EVM_ERC20,
+            _ => CoinType::Custom(name),
+        }
+    }
+}
+
+impl From<CoinType> for String {
+    fn from(coin_type: CoinType) -> Self {
+        coin_type.as_str().to_string()
+    }
+}
+
+impl std::fmt::Display for CoinType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 /// Attempt to load and parse the TOML config file as a `Config`.
 pub fn load(path: impl AsRef<Path>) -> Result<Config, Error> {
+    load_with(path, &CoinType::BUILTIN)
+}
+
+/// Like [`load`], accepting the `coin_types` of a registry with downstream
+/// sources, see [`crate::source::SourceRegistry::coin_types`].
+pub fn load_with(path: impl AsRef<Path>, coin_types: &[CoinType]) -> Result<Config, Error> {
     let config_toml = fs::read_to_string(&path).map_err(Error::config_io)?;
 
     let config = toml::from_str::<Config>(&config_toml[..]).map_err(Error::config_decode)?;
-    check_parse_u128(config.clone())?;
-    check_decimal_place(config.clone())?;
+    let problems = validate_with(&config, coin_types);
+    if !problems.is_empty() {
+        return Err(Error::config_invalid(ConfigProblems(problems)));
+    }
     Ok(config)
 }
 
-// Attempt to parse min_balance to u128 as min_balance is String toml while toml not support u128
-pub fn check_parse_u128(config: Config) -> Result<(), Error> {
-    for chain_config in config.chains.iter() {
-        for chain_address in chain_config.addresses.iter() {
-            for coin in chain_address.coins.iter() {
-                coin.min_balance
-                    .parse::<u128>()
-                    .map_err(Error::config_parse_u128)?;
+/// A problem found in a `Config`, located by the top-level table and, for
+/// chains, the indices of the address and coin it was found in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigProblem {
+    pub table: ConfigTable,
+    pub address: Option<usize>,
+    pub coin: Option<usize>,
+    /// Human readable location, e.g. `chain 'mantra-1', address 'mantra1...', denom 'uom'`.
+    pub context: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for ConfigProblem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.context, self.message)
+    }
+}
+
+/// A top-level table of the config, by index for arrays of tables.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConfigTable {
+    Chain(usize),
+    Webhook(usize),
+    Alertmanager,
+    Smtp,
+    Silence(usize),
+}
+
+/// Every problem found in a `Config`, one per line.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConfigProblems(pub Vec<ConfigProblem>);
+
+impl std::fmt::Display for ConfigProblems {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for problem in self.0.iter() {
+            write!(f, "\n  - {}", problem)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the whole config and report every problem found rather than the first one.
+pub fn validate(config: &Config) -> Vec<ConfigProblem> {
+    validate_with(config, &CoinType::BUILTIN)
+}
+
+/// Like [`validate`], accepting only coins of the given `coin_types`.
+pub fn validate_with(config: &Config, coin_types: &[CoinType]) -> Vec<ConfigProblem> {
+    let mut problems = Vec::new();
+    for (chain_index, chain_config) in config.chains.iter().enumerate() {
+        if config.chains[..chain_index]
+            .iter()
+            .any(|other| other.id == chain_config.id)
+        {
+            problems.push(ConfigProblem {
+                table: ConfigTable::Chain(chain_index),
+                address: None,
+                coin: None,
+                context: format!("chain '{}'", chain_config.id),
+                message: "chain id is configured more than once".to_string(),
+            });
+        }
+        for (address_index, chain_address) in chain_config.addresses.iter().enumerate() {
+            if chain_config.addresses[..address_index].iter().any(|other| {
+                other.address == chain_address.address && other.role == chain_address.role
+            }) {
+                problems.push(ConfigProblem {
+                    table: ConfigTable::Chain(chain_index),
+                    address: Some(address_index),
+                    coin: None,
+                    context: format!(
+                        "chain '{}', address '{}'",
+                        chain_config.id, chain_address.address
+                    ),
+                    message: format!(
+                        "address is configured more than once with role '{}'",
+                        chain_address.role
+                    ),
+                });
+            }
+            for message in check_address(chain_config, chain_address) {
+                problems.push(ConfigProblem {
+                    table: ConfigTable::Chain(chain_index),
+                    address: Some(address_index),
+                    coin: None,
+                    context: format!(
+                        "chain '{}', address '{}'",
+                        chain_config.id, chain_address.address
+                    ),
+                    message,
+                });
+            }
+            for (coin_index, coin) in chain_address.coins.iter().enumerate() {
+                let context = format!(
+                    "chain '{}', address '{}', denom '{}'",
+                    chain_config.id, chain_address.address, coin.denom
+                );
+                if chain_address.coins[..coin_index].iter().any(|other| {
+                    other.coin_type == coin.coin_type
+                        && other.denom == coin.denom
+                        && other.contract_address == coin.contract_address
+                }) {
+                    problems.push(ConfigProblem {
+                        table: ConfigTable::Chain(chain_index),
+                        address: Some(address_index),
+                        coin: Some(coin_index),
+                        context: context.clone(),
+                        message: "coin is configured more than once for the address, use critical_balance for a second threshold".to_string(),
+                    });
+                }
+                for message in check_coin(chain_config, coin, coin_types) {
+                    problems.push(ConfigProblem {
+                        table: ConfigTable::Chain(chain_index),
+                        address: Some(address_index),
+                        coin: Some(coin_index),
+                        context: context.clone(),
+                        message,
+                    });
+                }
             }
         }
     }
-    Ok(())
+    for (webhook_index, webhook) in config.webhooks.iter().enumerate() {
+        let duplicate = config.webhooks[..webhook_index]
+            .iter()
+            .any(|other| other.name == webhook.name);
+        let messages = duplicate
+            .then(|| "webhook name is configured more than once".to_string())
+            .into_iter()
+            .chain(check_webhook(webhook));
+        for message in messages {
+            problems.push(ConfigProblem {
+                table: ConfigTable::Webhook(webhook_index),
+                address: None,
+                coin: None,
+                context: format!("webhook '{}'", webhook.name),
+                message,
+            });
+        }
+    }
+    if let Some(alertmanager) = &config.alertmanager {
+        if let Some(message) = check_http_url(&alertmanager.url) {
+            problems.push(ConfigProblem {
+                table: ConfigTable::Alertmanager,
+                address: None,
+                coin: None,
+                context: "alertmanager".to_string(),
+                message,
+            });
+        }
+    }
+    if let Some(smtp) = &config.smtp {
+        for message in check_smtp(smtp) {
+            problems.push(ConfigProblem {
+                table: ConfigTable::Smtp,
+                address: None,
+                coin: None,
+                context: "smtp".to_string(),
+                message,
+            });
+        }
+    }
+    for (silence_index, silence) in config.silences.iter().enumerate() {
+        for message in check_silence(silence) {
+            problems.push(ConfigProblem {
+                table: ConfigTable::Silence(silence_index),
+                address: None,
+                coin: None,
+                context: format!("silence {}", silence_index + 1),
+                message,
+            });
+        }
+    }
+    problems
 }
 
-// Check decimal place
-pub fn check_decimal_place(config: Config) -> Result<(), Error> {
-    if config.chains.iter().any(|chain_config| {
-        chain_config.addresses.iter().any(|chain_address| {
-            chain_address
-                .coins
-                .iter()
-                .any(|coin| coin.decimal_place > 18)
-        })
-    }) {
-        return Err(Error::config_decimal_exceed(18));
+fn check_smtp(smtp: &SmtpConfig) -> Vec<String> {
+    let mut messages = Vec::new();
+    for mailbox in std::iter::once(&smtp.from).chain(smtp.to.iter()) {
+        if let Err(e) = mailbox.parse::<Mailbox>() {
+            messages.push(format!("mailbox '{}' is invalid: {}", mailbox, e));
+        }
     }
-    Ok(())
+    if smtp.to.is_empty() {
+        messages.push("to must list at least one mailbox".to_string());
+    }
+    let sources = [
+        smtp.password.is_some(),
+        smtp.password_env.is_some(),
+        smtp.password_file.is_some(),
+    ];
+    // the secret itself is read when the mail server is set up, so a config
+    // validates without it
+    let sources = sources.iter().filter(|set| **set).count();
+    if sources > 1 {
+        messages.push("set only one of password, password_env or password_file".to_string());
+    }
+    if smtp.username.is_some() != (sources > 0) {
+        messages.push("username and password must be set together".to_string());
+    }
+    if let Some(digest_at) = &smtp.digest_at {
+        messages.extend(parse_time_of_day(digest_at).err());
+    }
+    messages
 }
 
-// check contract address if it is CoinType::CW20 or CoinType::EvmErc20
-pub fn check_cw20_contract_address(config: Config) -> Result<(), Error> {
-    if config.chains.iter().any(|chain_config| {
-        chain_config.addresses.iter().any(|chain_address| {
-            chain_address.coins.iter().any(|coin| {
-                (coin.coin_type == CoinType::CW20 || coin.coin_type == CoinType::EVM_ERC20)
-                    && coin.contract_address.is_none()
-            })
-        })
-    }) {
-        return Err(Error::config_missing_c_w20_contract_address());
+fn check_http_url(url: &str) -> Option<String> {
+    match reqwest::Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") => None,
+        Ok(parsed) => Some(format!(
+            "url '{}' must be http or https, got {}",
+            url,
+            parsed.scheme()
+        )),
+        Err(e) => Some(format!("url '{}' is invalid: {}", url, e)),
     }
-    Ok(())
+}
+
+fn check_webhook(webhook: &WebhookConfig) -> Vec<String> {
+    let mut messages = Vec::new();
+    messages.extend(check_http_url(&webhook.url));
+    if let Some(template) = &webhook.template {
+        messages.extend(check_template(template).err());
+    }
+    messages
+}
+
+fn check_address(chain_config: &ChainConfig, chain_address: &Address) -> Vec<String> {
+    let mut messages = Vec::new();
+    let prefix = chain_config.bech32_prefix.as_deref();
+    let has_builtin_coin = chain_address
+        .coins
+        .iter()
+        .any(|coin| !matches!(coin.coin_type, CoinType::Custom(_)));
+    if is_hex_address(&chain_address.address) {
+        messages.extend(check_eip55(&chain_address.address).err());
+    } else if has_builtin_coin {
+        messages.extend(check_bech32(&chain_address.address, prefix).err());
+    }
+    if let Some(hex_address) = &chain_address.hex_address {
+        messages.extend(check_eip55(hex_address).err());
+    }
+    if !messages.is_empty() {
+        return messages;
+    }
+    let mut coin_types = chain_address
+        .coins
+        .iter()
+        .map(|coin| &coin.coin_type)
+        .collect::<Vec<_>>();
+    coin_types.dedup();
+    for coin_type in coin_types {
+        if let Err(message) = chain_address.query_address(coin_type, prefix) {
+            if !messages.contains(&message) {
+                messages.push(message);
+            }
+        }
+    }
+    messages
+}
+
+fn check_coin(chain_config: &ChainConfig, coin: &Coin, coin_types: &[CoinType]) -> Vec<String> {
+    let mut messages = Vec::new();
+    if !coin_types.contains(&coin.coin_type) {
+        let known = coin_types
+            .iter()
+            .map(CoinType::as_str)
+            .collect::<Vec<_>>()
+            .join(", ");
+        messages.push(format!(
+            "coin_type '{}' has no balance source, expected one of {}",
+            coin.coin_type, known
+        ));
+    }
+    // thresholds are Strings as toml does not support big integers
+    let min_balance = match coin.parse_threshold(&coin.min_balance) {
+        Ok(min_balance) => Some(min_balance),
+        Err(e) => {
+            messages.push(format!("min_balance {}", e));
+            None
+        }
+    };
+    if let Some(critical_balance) = &coin.critical_balance {
+        match (coin.parse_threshold(critical_balance), min_balance) {
+            (Err(e), _) => messages.push(format!("critical_balance {}", e)),
+            (Ok(critical), Some(min)) if critical > min => messages.push(format!(
+                "critical_balance '{}' must not exceed min_balance '{}'",
+                critical_balance, coin.min_balance
+            )),
+            _ => {}
+        }
+    }
+    if let Some(max_balance) = &coin.max_balance {
+        match (coin.parse_threshold(max_balance), min_balance) {
+            (Err(e), _) => messages.push(format!("max_balance {}", e)),
+            (Ok(max), Some(min)) if max <= min => messages.push(format!(
+                "max_balance '{}' must exceed min_balance '{}'",
+                max_balance, coin.min_balance
+            )),
+            _ => {}
+        }
+    }
+    if let Some(clear_balance) = &coin.clear_balance {
+        match (coin.parse_threshold(clear_balance), min_balance) {
+            (Err(e), _) => messages.push(format!("clear_balance {}", e)),
+            (Ok(clear), Some(min)) if clear < min => messages.push(format!(
+                "clear_balance '{}' must not be below min_balance '{}'",
+                clear_balance, coin.min_balance
+            )),
+            _ => {}
+        }
+    }
+    if coin.decimal_place > 18 {
+        messages.push(format!(
+            "decimal_place must not exceed 18, got {}",
+            coin.decimal_place
+        ));
+    }
+    match (&coin.coin_type, &coin.contract_address) {
+        (CoinType::CW20, Some(contract_address)) => messages
+            .extend(check_bech32(contract_address, chain_config.bech32_prefix.as_deref()).err()),
+        (CoinType::EVM_ERC20, Some(contract_address)) => {
+            messages.extend(check_eip55(contract_address).err())
+        }
+        (CoinType::CW20 | CoinType::EVM_ERC20, None) => messages.push(format!(
+            "coin_type {} requires a contract_address",
+            coin.coin_type
+        )),
+        _ => {}
+    }
+    match coin.coin_type {
+        CoinType::COSMOS | CoinType::CW20 if chain_config.grpc_addr.is_empty() => {
+            messages.push(format!(
+                "coin_type {} requires a grpc_addr on the chain",
+                coin.coin_type
+            ));
+        }
+        CoinType::EVM | CoinType::EVM_ERC20 if chain_config.evm_addr.is_empty() => {
+            messages.push(format!(
+                "coin_type {} requires an evm_addr on the chain",
+                coin.coin_type
+            ));
+        }
+        _ => {}
+    }
+    messages
 }
 
 /// Serialize the given `Config` as TOML to the given config file.
@@ -172,7 +946,7 @@ pub fn store(config: &Config, path: impl AsRef<Path>) -> Result<(), Error> {
 }
 
 /// Serialize the given `Config` as TOML to the given writer.
-pub(crate) fn store_writer(config: &Config, mut writer: impl Write) -> Result<(), Error> {
+pub fn store_writer(config: &Config, mut writer: impl Write) -> Result<(), Error> {
     let toml_config = toml::to_string_pretty(&config).map_err(Error::config_encode)?;
 
     writeln!(writer, "{}", toml_config).map_err(Error::config_io)?;
@@ -182,7 +956,11 @@ pub(crate) fn store_writer(config: &Config, mut writer: impl Write) -> Result<()
 
 #[cfg(test)]
 mod tests {
-    use super::{load, store_writer};
+    use super::{
+        bech32_to_hex, check_smtp, default, fs, load, store_writer, validate, validate_with,
+        Address, Coin, CoinType, Config, ConfigTable, Duration, EndpointSelection,
+        PrometheusConfig, SmtpConfig, SmtpSecurity, U256,
+    };
     use test_log::test;
 
     #[test]
@@ -195,6 +973,12 @@ mod tests {
         let config = load(path);
         println!("{:?}", config);
         assert!(config.is_ok());
+        let prometheus = config.unwrap().prometheus;
+        assert_eq!(
+            prometheus.series_ttl(Duration::from_secs(60)),
+            Some(Duration::from_secs(300))
+        );
+        assert_eq!(PrometheusConfig::default().expire_after_refreshes, 3);
     }
 
     #[test]
@@ -209,6 +993,390 @@ mod tests {
         assert!(config.is_err());
     }
 
+    #[test]
+    fn parse_endpoint_lists() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/config/fixtures/chains.toml"
+        );
+
+        let config = load(path).expect("could not parse config");
+        let chains = config.chains_map();
+        assert_eq!(chains[&"chain_A".to_string()].grpc_addr.len(), 1);
+        assert_eq!(chains[&"chain_B".to_string()].grpc_addr.len(), 2);
+        assert_eq!(
+            chains[&"chain_B".to_string()].endpoint_selection,
+            EndpointSelection::RoundRobin
+        );
+    }
+
+    #[test]
+    fn thresholds_accept_display_units() {
+        let coin = toml::from_str::<Coin>(
+            "denom = 'wei'\ndisplay_denom = 'ETH'\ndecimal_place = 18\nmin_balance = '10 ETH'\n",
+        )
+        .unwrap();
+        let eth = |amount: u64| U256::exp10(18) * amount;
+        assert_eq!(coin.parse_threshold(&coin.min_balance), Ok(eth(10)));
+        assert_eq!(coin.parse_threshold("0.5 eth"), Ok(eth(1) / 2));
+        assert_eq!(coin.parse_threshold("1.5ETH"), Ok(eth(3) / 2));
+        assert_eq!(coin.parse_threshold("2000 wei"), Ok(U256::from(2000)));
+        assert_eq!(coin.parse_threshold("2000"), Ok(U256::from(2000)));
+
+        let problems = [
+            "1.5",
+            "10 OM",
+            "ETH",
+            "1.5 wei",
+            "0.0000000000000000001 ETH",
+        ]
+        .map(|value| coin.parse_threshold(value).unwrap_err());
+        assert!(problems[0].contains("needs a unit, e.g. '1.5 ETH'"));
+        assert!(problems[1].contains("neither the display denom 'ETH' nor the denom 'wei'"));
+        assert!(problems[2].contains("is not an amount"));
+        assert!(problems[3].contains("e.g. '1.5 ETH'"));
+        assert!(problems[4].contains("more than 18 decimal places"));
+
+        // without a display_denom, the denom is in atomic units
+        let coin = toml::from_str::<Coin>("denom = 'uom'\nmin_balance = '500000 uom'\n").unwrap();
+        assert_eq!(
+            coin.parse_threshold(&coin.min_balance),
+            Ok(U256::from(500_000))
+        );
+        assert_eq!(
+            coin.parse_threshold("500000 UOM").unwrap_err(),
+            "'500000 UOM' has unit 'UOM', not the denom 'uom'"
+        );
+        assert!(coin
+            .parse_threshold("0.5 uom")
+            .unwrap_err()
+            .contains("set display_denom"));
+    }
+
+    #[test]
+    fn validate_reports_every_problem() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [[chains]]
+            id = 'chain_A'
+            [[chains.addresses]]
+            address = 'mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0'
+            role = 'relayer'
+            [[chains.addresses.coins]]
+            denom = 'uom'
+            min_balance = 'lots'
+            decimal_place = 19
+            [[chains.addresses.coins]]
+            coin_type = 'CW20'
+            denom = 'TEST'
+            min_balance = '1'
+
+            [[chains]]
+            id = 'chain_B'
+            grpc_addr = 'http://127.0.0.1:9090'
+            [[chains.addresses]]
+            address = '0xAb5801a7D398351b8bE11C439e05C5B3259aeC9B'
+            role = 'someone'
+            [[chains.addresses.coins]]
+            coin_type = 'EVM_ERC20'
+            contract_address = '0xd2b95283011E47257917770D28Bb3EE44c849f6F'
+            denom = 'USDC'
+            min_balance = '1'
+            "#,
+        )
+        .unwrap();
+
+        let problems = validate(&config);
+        let messages = problems
+            .iter()
+            .map(|problem| problem.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "chain 'chain_A', address 'mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0', denom 'uom': min_balance 'lots' is not an amount",
+                "chain 'chain_A', address 'mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0', denom 'uom': decimal_place must not exceed 18, got 19",
+                "chain 'chain_A', address 'mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0', denom 'uom': coin_type COSMOS requires a grpc_addr on the chain",
+                "chain 'chain_A', address 'mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0', denom 'TEST': coin_type CW20 requires a contract_address",
+                "chain 'chain_A', address 'mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0', denom 'TEST': coin_type CW20 requires a grpc_addr on the chain",
+                "chain 'chain_B', address '0xAb5801a7D398351b8bE11C439e05C5B3259aeC9B', denom 'USDC': coin_type EVM_ERC20 requires an evm_addr on the chain",
+            ]
+        );
+        assert_eq!(problems[3].table, ConfigTable::Chain(0));
+        assert_eq!(problems[3].coin, Some(1));
+        assert_eq!(problems[5].table, ConfigTable::Chain(1));
+    }
+
+    #[test]
+    fn validate_reports_bad_addresses() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [[chains]]
+            id = 'chain_A'
+            bech32_prefix = 'mantra'
+            grpc_addr = 'http://127.0.0.1:9090'
+            [[chains.addresses]]
+            address = 'mantra1x5nk33zpglp4ge6q9a8xx3zceqf4g8nvaggjmd'
+            role = 'relayer'
+            [[chains.addresses.coins]]
+            coin_type = 'CW20'
+            contract_address = 'cosmos1x5nk33zpglp4ge6q9a8xx3zceqf4g8nvkrzkcz'
+            denom = 'TEST'
+            min_balance = '1'
+
+            [[chains]]
+            id = 'chain_B'
+            evm_addr = 'http://127.0.0.1:8545'
+            [[chains.addresses]]
+            address = '0xAb5801a7D398351b8bE11C439e05C5B3259aec9B'
+            role = 'someone'
+            [[chains.addresses.coins]]
+            coin_type = 'EVM'
+            denom = 'wei'
+            min_balance = '1'
+            "#,
+        )
+        .unwrap();
+
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 3, "{:#?}", problems);
+        assert!(problems[0].message.contains("not a valid bech32 address"));
+        assert_eq!(problems[0].coin, None);
+        assert!(problems[1].message.contains("expected 'mantra'"));
+        assert_eq!(problems[1].coin, Some(0));
+        assert!(problems[2].message.contains("EIP-55"));
+    }
+
+    #[test]
+    fn validate_reports_duplicate_coins_and_thresholds() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [[chains]]
+            id = 'chain_A'
+            grpc_addr = 'http://127.0.0.1:9090'
+            [[chains.addresses]]
+            address = 'mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0'
+            role = 'relayer'
+            [[chains.addresses.coins]]
+            denom = 'uom'
+            warning_balance = '100'
+            critical_balance = '200'
+            max_balance = '100'
+            clear_balance = '50'
+            for = '5m'
+            [[chains.addresses.coins]]
+            denom = 'uom'
+            min_balance = '400'
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.chains[0].addresses[0].coins[0].min_balance, "100");
+        assert_eq!(
+            config.chains[0].addresses[0].coins[0].for_duration,
+            Some(Duration::from_secs(300))
+        );
+
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 4, "{:#?}", problems);
+        assert!(problems[0].message.contains("must not exceed min_balance"));
+        assert!(problems[1].message.contains("must exceed min_balance"));
+        assert!(problems[2]
+            .message
+            .contains("must not be below min_balance"));
+        assert_eq!(problems[3].coin, Some(1));
+        assert!(problems[3].message.contains("more than once"));
+    }
+
+    #[test]
+    fn query_address_per_coin_type() {
+        let chain_address = Address {
+            address: "mantra1x5nk33zpglp4ge6q9a8xx3zceqf4g8nvaggjmc".to_string(),
+            hex_address: None,
+            role: "relayer".to_string(),
+            disable_balance: None,
+            balance_url: None,
+            refresh: default::refresh(),
+            spend_window: default::spend_window(),
+            coins: Vec::new(),
+        };
+        let hex = chain_address.query_address(&CoinType::EVM, None).unwrap();
+        assert_eq!(hex, bech32_to_hex(&chain_address.address).unwrap());
+        assert_eq!(
+            chain_address.query_address(&CoinType::CW20, None).unwrap(),
+            chain_address.address
+        );
+
+        let hex_address = Address {
+            address: hex.clone(),
+            ..chain_address.clone()
+        };
+        assert_eq!(
+            hex_address
+                .query_address(&CoinType::COSMOS, Some("mantra"))
+                .unwrap(),
+            chain_address.address
+        );
+        assert!(hex_address.query_address(&CoinType::COSMOS, None).is_err());
+        assert_eq!(
+            hex_address
+                .query_address(&CoinType::EVM_ERC20, None)
+                .unwrap(),
+            hex
+        );
+
+        // an explicit hex_address wins over the derived one
+        let overridden = Address {
+            hex_address: Some("0xAb5801a7D398351b8bE11C439e05C5B3259aeC9B".to_string()),
+            ..chain_address
+        };
+        assert_eq!(
+            overridden.query_address(&CoinType::EVM, None).unwrap(),
+            "0xAb5801a7D398351b8bE11C439e05C5B3259aeC9B"
+        );
+        // metrics keep the configured hex_address as their label
+        assert_eq!(
+            overridden.label(),
+            "0xAb5801a7D398351b8bE11C439e05C5B3259aeC9B"
+        );
+        assert_eq!(hex_address.label(), hex);
+    }
+
+    #[test]
+    fn validate_reports_notifier_problems() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [[webhooks]]
+            name = 'ops'
+            url = 'https://hooks.example.com/balance'
+            template = '{"text": "{{denom}} of {{role}} is {{balance}}"}'
+
+            [[webhooks]]
+            name = 'ops'
+            url = 'ftp://hooks.example.com'
+            template = '{"text": "{{balanse}}"}'
+
+            [alertmanager]
+            url = 'alertmanager:9093'
+
+            [smtp]
+            host = 'smtp.example.com'
+            username = 'watcher'
+            from = 'watcher'
+            to = ['finance@example.com']
+            digest_at = '8:30am'
+
+            [[silences]]
+            ends_at = '2024-06-01T14:00:00Z'
+
+            [api]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(config.webhooks[0].retries, 3);
+        assert!(config.webhooks[0].send_resolved);
+        let api = config.api.as_ref().unwrap();
+        assert_eq!((api.host.as_str(), api.port), ("127.0.0.1", 9091));
+        let smtp = config.smtp.as_ref().unwrap();
+        assert_eq!((smtp.port, smtp.security), (587, SmtpSecurity::Starttls));
+
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 8, "{:#?}", problems);
+        assert!(problems[..3]
+            .iter()
+            .all(|problem| problem.table == ConfigTable::Webhook(1)));
+        assert!(problems[0].message.contains("more than once"));
+        assert!(problems[1].message.contains("must be http or https"));
+        assert!(problems[2].message.contains("unknown placeholders"));
+        assert_eq!(problems[3].table, ConfigTable::Alertmanager);
+        assert!(problems[4..7]
+            .iter()
+            .all(|problem| problem.table == ConfigTable::Smtp));
+        assert!(problems[4].message.contains("mailbox 'watcher' is invalid"));
+        assert!(problems[5].message.contains("set together"));
+        assert!(problems[6].message.contains("HH:MM"));
+        assert_eq!(problems[7].table, ConfigTable::Silence(0));
+        assert!(problems[7].message.contains("at least one of"));
+    }
+
+    #[test]
+    fn smtp_password_is_kept_out_of_the_config() {
+        let path = std::env::temp_dir().join(format!("smtp-password-{}", std::process::id()));
+        fs::write(&path, "secret\n").unwrap();
+        let smtp = |password: &str| {
+            toml::from_str::<SmtpConfig>(&format!(
+                "host = 'smtp.example.com'\nfrom = 'watcher@example.com'\nto = ['finance@example.com']\nusername = 'watcher'\n{}",
+                password
+            ))
+            .unwrap()
+        };
+
+        let from_file = smtp(&format!("password_file = '{}'", path.display()));
+        assert_eq!(from_file.password(), Ok(Some("secret".to_string())));
+        std::env::set_var("BALANCE_WATCHER_TEST_SMTP_PASSWORD", "from-env");
+        let from_env = smtp("password_env = 'BALANCE_WATCHER_TEST_SMTP_PASSWORD'");
+        assert_eq!(from_env.password(), Ok(Some("from-env".to_string())));
+        fs::remove_file(&path).unwrap();
+        assert!(from_file.password().unwrap_err().contains("password_file"));
+        // validated without the secret at hand
+        assert!(check_smtp(&from_file).is_empty());
+        assert!(check_smtp(&smtp("password_env = 'BALANCE_WATCHER_TEST_UNSET'")).is_empty());
+
+        let inline = smtp("password = 'secret'");
+        assert!(!toml::to_string(&inline).unwrap().contains("secret"));
+        let both = smtp("password = 'secret'\npassword_env = 'BALANCE_WATCHER_TEST_SMTP_PASSWORD'");
+        assert!(check_smtp(&both)[0].contains("only one of"));
+    }
+
+    #[test]
+    fn validate_rejects_coin_types_without_source() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [[chains]]
+            id = 'chain_A'
+            [[chains.addresses]]
+            address = 'mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0'
+            role = 'relayer'
+            [[chains.addresses.coins]]
+            coin_type = 'COSMSO'
+            denom = 'uom'
+            min_balance = '1'
+            "#,
+        )
+        .unwrap();
+
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 1, "{:#?}", problems);
+        assert_eq!(
+            problems[0].message,
+            "coin_type 'COSMSO' has no balance source, expected one of COSMOS, CW20, EVM, EVM_ERC20"
+        );
+        let coin_types = [CoinType::from("COSMSO".to_string())];
+        assert!(validate_with(&config, &coin_types).is_empty());
+    }
+
+    #[test]
+    fn validate_reports_underivable_addresses() {
+        let config = toml::from_str::<Config>(
+            r#"
+            [[chains]]
+            id = 'chain_A'
+            grpc_addr = 'http://127.0.0.1:9090'
+            [[chains.addresses]]
+            address = '0xAb5801a7D398351b8bE11C439e05C5B3259aeC9B'
+            role = 'relayer'
+            [[chains.addresses.coins]]
+            coin_type = 'COSMOS'
+            denom = 'uom'
+            min_balance = '1'
+            "#,
+        )
+        .unwrap();
+
+        let problems = validate(&config);
+        assert_eq!(problems.len(), 1, "{:#?}", problems);
+        assert!(problems[0].message.contains("set bech32_prefix"));
+    }
+
     #[test]
     fn serialize_valid_config() {
         let path = concat!(
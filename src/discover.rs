@@ -0,0 +1,343 @@
+//! Coin entries bootstrapped from what an address holds
+use crate::amount::{Amount, Balance};
+use crate::config::{self, CoinType, Config};
+use crate::error::Error;
+use crate::grpc::grpc_endpoint;
+use crate::query::{query_cosmos_balances, query_denom_metadata, query_denom_trace, DenomTrace};
+use crate::source::ChainEndpoints;
+use cosmos_sdk_proto::cosmos::bank::v1beta1::Metadata;
+use http::uri::Uri;
+use std::io::Write;
+use toml_edit::{DocumentMut, Item};
+use web3::types::U256;
+
+/// A coin entry for `balance`, named and scaled after the bank `metadata` of
+/// its denom when the chain has some, and whether its `decimal_place` is a
+/// guess. Without metadata, the name and decimals are guessed from the base
+/// denom of the IBC `trace`, or the denom itself. Decimals beyond what the
+/// config accepts are guessed too.
+pub fn suggest_coin(
+    balance: &Balance,
+    metadata: Option<&Metadata>,
+    trace: Option<&DenomTrace>,
+) -> (config::Coin, bool) {
+    let display_unit = metadata.and_then(|metadata| {
+        metadata
+            .denom_units
+            .iter()
+            .find(|unit| unit.denom == metadata.display)
+            .map(|unit| (metadata, unit.exponent))
+    });
+    let min_balance = suggest_min_balance(balance.amount);
+    let (display_denom, decimal_place, min_balance, guessed) = match display_unit {
+        Some((metadata, exponent)) if exponent <= 18 => {
+            let name = if metadata.symbol.is_empty() {
+                metadata.display.to_uppercase()
+            } else {
+                metadata.symbol.clone()
+            };
+            let min_balance = Amount::new(min_balance, exponent);
+            let min_balance = format!("{} {}", min_balance, name);
+            (name, exponent, min_balance, false)
+        }
+        // the decimals are unknown, so is the whole unit
+        _ => {
+            let (name, decimal_place) = match trace {
+                Some(trace) => guess_display_unit(&trace.base_denom),
+                None => (
+                    fallback_display_denom(&balance.denom),
+                    config::default::decimal_place(),
+                ),
+            };
+            let min_balance = format!("{} {}", min_balance, balance.denom);
+            (name, decimal_place, min_balance, true)
+        }
+    };
+    let coin = config::Coin {
+        min_balance,
+        critical_balance: None,
+        max_balance: None,
+        clear_balance: None,
+        for_duration: None,
+        denom: balance.denom.clone(),
+        display_denom: Some(display_denom),
+        contract_address: None,
+        decimal_place,
+        display_precision: None,
+        coin_type: CoinType::COSMOS,
+    };
+    (coin, guessed)
+}
+
+/// A display name and decimals guessed from the prefix of a base denom:
+/// `uatom` is `ATOM` with 6 decimals and `aevmos` is `EVMOS` with 18.
+fn guess_display_unit(base_denom: &str) -> (String, u32) {
+    let name = fallback_display_denom(base_denom);
+    let prefixed = |prefix| name.strip_prefix(prefix).filter(|rest| !rest.is_empty());
+    if let Some(rest) = prefixed('u') {
+        (rest.to_uppercase(), 6)
+    } else if let Some(rest) = prefixed('a') {
+        (rest.to_uppercase(), 18)
+    } else {
+        (name.to_uppercase(), config::default::decimal_place())
+    }
+}
+
+/// The subdenom of a tokenfactory denom (`factory/{creator}/{subdenom}`),
+/// the denom itself otherwise.
+fn fallback_display_denom(denom: &str) -> String {
+    match denom.strip_prefix("factory/") {
+        Some(rest) => rest.rsplit('/').next().unwrap_or(rest).to_string(),
+        None => denom.to_string(),
+    }
+}
+
+/// A tenth of `amount`, rounded down to its leading digit, as a starting
+/// point to tune. Suggested in whole display units.
+pub fn suggest_min_balance(amount: U256) -> U256 {
+    let tenth = amount / 10;
+    if tenth.is_zero() {
+        return tenth;
+    }
+    let digits = tenth.to_string().len();
+    let unit = U256::exp10(digits - 1);
+    tenth - tenth % unit
+}
+
+/// A config holding one chain and address, from [`discover`].
+pub struct Discovered {
+    pub config: Config,
+    /// Denoms whose `decimal_place` could not be read from the chain.
+    pub guessed_decimals: Vec<String>,
+}
+
+impl Discovered {
+    /// Writes the config as [`config::store_writer`] does, with a comment
+    /// above every coin whose `decimal_place` is a guess.
+    pub fn store_writer(&self, mut writer: impl Write) -> Result<(), Error> {
+        let mut toml_config = Vec::new();
+        config::store_writer(&self.config, &mut toml_config)?;
+        let Ok(mut document) = String::from_utf8_lossy(&toml_config).parse::<DocumentMut>() else {
+            return writer.write_all(&toml_config).map_err(Error::config_io);
+        };
+        let coins = document
+            .get_mut("chains")
+            .and_then(Item::as_array_of_tables_mut)
+            .and_then(|chains| chains.get_mut(0))
+            .and_then(|chain| chain.get_mut("addresses"))
+            .and_then(Item::as_array_of_tables_mut)
+            .and_then(|addresses| addresses.get_mut(0))
+            .and_then(|address| address.get_mut("coins"))
+            .and_then(Item::as_array_of_tables_mut);
+        for coin in coins.into_iter().flat_map(|coins| coins.iter_mut()) {
+            let guessed = coin
+                .get("denom")
+                .and_then(Item::as_str)
+                .is_some_and(|denom| self.guessed_decimals.iter().any(|guessed| guessed == denom));
+            if guessed {
+                coin.decor_mut().set_prefix(
+                    "\n# decimal_place is a guess, check it before relying on the balance\n",
+                );
+            }
+        }
+        write!(writer, "{}", document).map_err(Error::config_io)
+    }
+}
+
+/// Bank balances of `address` on `chain_id` as a config holding just that
+/// chain and address.
+pub async fn discover(
+    config: &Config,
+    chain_id: &str,
+    address: &str,
+    role: &str,
+) -> Result<Discovered, Error> {
+    let chain_config = config
+        .chains
+        .iter()
+        .find(|chain_config| chain_config.id == chain_id)
+        .ok_or_else(|| Error::unknown_chain(chain_id.to_string()))?;
+    let chain_address = config::Address {
+        address: address.to_string(),
+        hex_address: None,
+        role: role.to_string(),
+        disable_balance: None,
+        balance_url: None,
+        refresh: config::default::refresh(),
+        spend_window: config::default::spend_window(),
+        coins: Vec::new(),
+    };
+    let query_address = chain_address
+        .query_address(&CoinType::COSMOS, chain_config.bech32_prefix.as_deref())
+        .map_err(Error::invalid_address)?;
+    let endpoints = ChainEndpoints::from_config(chain_config);
+    let (suggested, _) = endpoints
+        .grpc
+        .execute(&CoinType::COSMOS, "*", |grpc_addr| {
+            let query_address = query_address.clone();
+            async move {
+                let channel = grpc_endpoint(grpc_addr.parse::<Uri>()?)?.connect().await?;
+                let mut suggested = Vec::new();
+                for balance in query_cosmos_balances(channel.clone(), query_address).await? {
+                    let metadata =
+                        query_denom_metadata(channel.clone(), balance.denom.clone()).await?;
+                    let trace = match balance.denom.strip_prefix("ibc/") {
+                        Some(hash) if metadata.is_none() => {
+                            query_denom_trace(channel.clone(), hash.to_string()).await?
+                        }
+                        _ => None,
+                    };
+                    suggested.push(suggest_coin(&balance, metadata.as_ref(), trace.as_ref()));
+                }
+                Ok(suggested)
+            }
+        })
+        .await?;
+    let guessed_decimals = suggested
+        .iter()
+        .filter(|(_, guessed)| *guessed)
+        .map(|(coin, _)| coin.denom.clone())
+        .collect();
+    let coins = suggested.into_iter().map(|(coin, _)| coin).collect();
+    let config = Config {
+        chains: vec![config::ChainConfig {
+            addresses: vec![config::Address {
+                coins,
+                ..chain_address
+            }],
+            ..chain_config.clone()
+        }],
+        ..Default::default()
+    };
+    Ok(Discovered {
+        config,
+        guessed_decimals,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cosmos_sdk_proto::cosmos::bank::v1beta1::DenomUnit;
+
+    fn balance(denom: &str, amount: u64) -> Balance {
+        Balance {
+            denom: denom.to_string(),
+            amount: U256::from(amount),
+        }
+    }
+
+    #[test]
+    fn min_balance_is_a_rounded_tenth() {
+        assert_eq!(
+            suggest_min_balance(U256::from(123_456_789u64)),
+            U256::from(10_000_000u64)
+        );
+        assert_eq!(suggest_min_balance(U256::from(99u64)), U256::from(9u64));
+        assert_eq!(suggest_min_balance(U256::from(5u64)), U256::zero());
+    }
+
+    #[test]
+    fn coin_follows_denom_metadata() {
+        let metadata = Metadata {
+            denom_units: vec![
+                DenomUnit {
+                    denom: "uom".to_string(),
+                    exponent: 0,
+                    aliases: vec![],
+                },
+                DenomUnit {
+                    denom: "om".to_string(),
+                    exponent: 6,
+                    aliases: vec![],
+                },
+            ],
+            base: "uom".to_string(),
+            display: "om".to_string(),
+            symbol: "OM".to_string(),
+            ..Default::default()
+        };
+        let (coin, guessed) = suggest_coin(&balance("uom", 2_500_000_000), Some(&metadata), None);
+        assert!(!guessed);
+        assert_eq!(coin.display_denom.as_deref(), Some("OM"));
+        assert_eq!(coin.decimal_place, 6);
+        assert_eq!(coin.min_balance, "200 OM");
+    }
+
+    #[test]
+    fn coin_without_metadata_uses_defaults() {
+        let (coin, guessed) = suggest_coin(&balance("factory/mantra1abc/uusdc", 10), None, None);
+        assert!(guessed);
+        assert_eq!(coin.display_denom.as_deref(), Some("uusdc"));
+        assert_eq!(coin.decimal_place, 6);
+        assert_eq!(coin.min_balance, "1 factory/mantra1abc/uusdc");
+
+        let (coin, _) = suggest_coin(&balance(IBC_DENOM, 10), None, None);
+        assert_eq!(coin.display_denom.as_deref(), Some(IBC_DENOM));
+    }
+
+    const IBC_DENOM: &str = "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2";
+
+    fn trace(base_denom: &str) -> DenomTrace {
+        DenomTrace {
+            path: "transfer/channel-0".to_string(),
+            base_denom: base_denom.to_string(),
+        }
+    }
+
+    #[test]
+    fn ibc_coin_is_named_after_its_base_denom() {
+        let (coin, guessed) = suggest_coin(&balance(IBC_DENOM, 10), None, Some(&trace("uusdc")));
+        assert!(guessed);
+        assert_eq!(coin.denom, IBC_DENOM);
+        assert_eq!(coin.display_denom.as_deref(), Some("USDC"));
+        assert_eq!(coin.decimal_place, 6);
+        assert_eq!(coin.min_balance, format!("1 {}", IBC_DENOM));
+
+        let (coin, _) = suggest_coin(&balance(IBC_DENOM, 10), None, Some(&trace("aevmos")));
+        assert_eq!(coin.display_denom.as_deref(), Some("EVMOS"));
+        assert_eq!(coin.decimal_place, 18);
+        let (coin, _) = suggest_coin(&balance(IBC_DENOM, 10), None, Some(&trace("inj")));
+        assert_eq!(coin.display_denom.as_deref(), Some("INJ"));
+        assert_eq!(coin.decimal_place, 6);
+    }
+
+    #[test]
+    fn guessed_decimals_are_flagged() {
+        let coin = |denom: &str| suggest_coin(&balance(denom, 10), None, None).0;
+        let mut config: Config = toml::from_str(
+            "[[chains]]\nid = 'mantra-1'\n[[chains.addresses]]\naddress = 'mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0'\nrole = 'faucet'\n",
+        )
+        .unwrap();
+        config.chains[0].addresses[0].coins = vec![coin("uom"), coin(IBC_DENOM)];
+        let discovered = Discovered {
+            config,
+            guessed_decimals: vec![IBC_DENOM.to_string()],
+        };
+        let mut toml_config = Vec::new();
+        discovered.store_writer(&mut toml_config).unwrap();
+        let toml_config = String::from_utf8(toml_config).unwrap();
+        let mut stored = Vec::new();
+        config::store_writer(&discovered.config, &mut stored).unwrap();
+        // the same config as the config module writes, only commented
+        assert_eq!(
+            toml_config.replace(
+                "\n# decimal_place is a guess, check it before relying on the balance\n",
+                "\n"
+            ),
+            String::from_utf8(stored).unwrap()
+        );
+        let lines = toml_config.lines().collect::<Vec<_>>();
+        let flagged = lines
+            .iter()
+            .position(|line| line.starts_with("# decimal_place is a guess"))
+            .unwrap();
+        assert_eq!(lines[flagged + 1], "[[chains.addresses.coins]]");
+        let denom = format!("denom = \"{}\"", IBC_DENOM);
+        assert!(lines[flagged + 2..]
+            .iter()
+            .take_while(|line| !line.starts_with('['))
+            .any(|line| *line == denom));
+        assert_eq!(toml_config.matches("# decimal_place").count(), 1);
+    }
+}
@@ -0,0 +1,300 @@
+//! Email alerts and a daily digest of every watched balance
+use crate::check::write_table;
+use crate::config::{SmtpConfig, SmtpSecurity};
+use crate::error::Error;
+use crate::notify::{Alert, Notifier};
+use async_trait::async_trait;
+use lettre::message::{header::ContentType, Mailbox};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use std::fmt;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const DAY: Duration = Duration::from_secs(24 * 3600);
+
+/// Parse a UTC time of day written `HH:MM` into the time since midnight.
+pub fn parse_time_of_day(value: &str) -> Result<Duration, String> {
+    let invalid = || format!("time of day '{}' must be written HH:MM", value);
+    let (hours, minutes) = value.split_once(':').ok_or_else(invalid)?;
+    let hours = hours.parse::<u64>().map_err(|_| invalid())?;
+    let minutes = minutes.parse::<u64>().map_err(|_| invalid())?;
+    if hours > 23 || minutes > 59 {
+        return Err(invalid());
+    }
+    Ok(Duration::from_secs(hours * 3600 + minutes * 60))
+}
+
+/// How long from `now` until the next `time_of_day` in UTC.
+pub fn until_time_of_day(now: SystemTime, time_of_day: Duration) -> Duration {
+    let since_midnight = Duration::from_secs(
+        now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() % DAY.as_secs(),
+    );
+    if since_midnight < time_of_day {
+        time_of_day - since_midnight
+    } else {
+        DAY + time_of_day - since_midnight
+    }
+}
+
+/// Sends alerts and digests to the mailboxes of an [`SmtpConfig`].
+pub struct Email {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl Email {
+    pub fn new(config: &SmtpConfig) -> Result<Self, Error> {
+        let error = |e: &dyn fmt::Display| Error::notify(config.host.clone(), e.to_string());
+        let builder = match config.security {
+            SmtpSecurity::Starttls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)
+                    .map_err(|e| error(&e))?
+            }
+            SmtpSecurity::Tls => {
+                AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host).map_err(|e| error(&e))?
+            }
+            SmtpSecurity::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host)
+            }
+        };
+        let builder = builder.port(config.port).timeout(Some(config.timeout));
+        let builder = match (&config.username, config.password().map_err(|e| error(&e))?) {
+            (Some(username), Some(password)) => {
+                builder.credentials(Credentials::new(username.clone(), password))
+            }
+            _ => builder,
+        };
+        let to = config
+            .to
+            .iter()
+            .map(|to| to.parse::<Mailbox>().map_err(|e| error(&e)))
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            transport: builder.build(),
+            from: config.from.parse().map_err(|e| error(&e))?,
+            to,
+        })
+    }
+
+    /// Mail `body` as plain text to every recipient.
+    pub async fn send(&self, subject: &str, body: String) -> Result<(), Error> {
+        let error = |e: &dyn fmt::Display| Error::notify("smtp".to_string(), e.to_string());
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .subject(subject)
+            .header(ContentType::TEXT_PLAIN);
+        for to in self.to.iter() {
+            builder = builder.to(to.clone());
+        }
+        let message = builder.body(body).map_err(|e| error(&e))?;
+        self.transport.send(message).await.map_err(|e| error(&e))?;
+        Ok(())
+    }
+
+    /// Mail the latest balance of every watched coin and when it was queried.
+    pub async fn send_digest(&self, balances: &[(Alert, SystemTime)]) -> Result<(), Error> {
+        let subject = format!("Balance digest: {} coins", balances.len());
+        self.send(&subject, DigestTable(balances).to_string()).await
+    }
+}
+
+#[async_trait]
+impl Notifier for Email {
+    async fn notify(&self, alert: &Alert) -> Result<(), Error> {
+        let subject = format!(
+            "[{}] {} on {} has {} {}",
+            alert.status.as_str(),
+            alert.role,
+            alert.chain_id,
+            alert.balance,
+            alert.denom
+        );
+        let body = format!(
+            "The balance of {} ({}) on {} is {} {}, min_balance is {} {}.\n{}",
+            alert.address,
+            alert.role,
+            alert.chain_id,
+            alert.balance,
+            alert.denom,
+            alert.threshold,
+            alert.denom,
+            alert.balance_url
+        );
+        self.send(&subject, body).await
+    }
+}
+
+/// Latest balances and when they were queried as a table, in the order given.
+pub struct DigestTable<'a>(pub &'a [(Alert, SystemTime)]);
+
+impl fmt::Display for DigestTable<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header = [
+            "CHAIN",
+            "ROLE",
+            "ADDRESS",
+            "DENOM",
+            "BALANCE",
+            "MIN_BALANCE",
+            "STATUS",
+            "UPDATED",
+        ]
+        .map(String::from);
+        let rows = self
+            .0
+            .iter()
+            .map(|(alert, updated)| {
+                [
+                    alert.chain_id.clone(),
+                    alert.role.clone(),
+                    alert.address.clone(),
+                    alert.denom.clone(),
+                    alert.balance.clone(),
+                    alert.threshold.clone(),
+                    alert.severity.clone(),
+                    humantime::format_rfc3339_seconds(*updated).to_string(),
+                ]
+            })
+            .collect::<Vec<_>>();
+        write_table(f, &header, &rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CoinType;
+    use crate::notify::{AlertStatus, CoinId};
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    /// Accepts every mail over plain SMTP on a local port and forwards its data.
+    async fn smtp_sink() -> (u16, mpsc::UnboundedReceiver<String>) {
+        let listener = TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, received) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    writer.write_all(b"220 sink ESMTP\r\n").await.unwrap();
+                    let mut data = None::<String>;
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        let reply: &[u8] = match data.as_mut() {
+                            Some(body) if line == "." => {
+                                sender.send(std::mem::take(body)).unwrap();
+                                data = None;
+                                b"250 queued\r\n"
+                            }
+                            Some(body) => {
+                                body.push_str(&line);
+                                body.push('\n');
+                                continue;
+                            }
+                            None if line.starts_with("DATA") => {
+                                data = Some(String::new());
+                                b"354 go ahead\r\n"
+                            }
+                            None if line.starts_with("QUIT") => {
+                                let _ = writer.write_all(b"221 bye\r\n").await;
+                                break;
+                            }
+                            None => b"250 ok\r\n",
+                        };
+                        writer.write_all(reply).await.unwrap();
+                    }
+                });
+            }
+        });
+        (port, received)
+    }
+
+    fn email(port: u16) -> Email {
+        let config = toml::from_str::<SmtpConfig>(&format!(
+            "host = '127.0.0.1'\nport = {}\nsecurity = 'none'\nfrom = 'Watcher <watcher@example.com>'\nto = ['finance@example.com']\n",
+            port
+        ))
+        .unwrap();
+        Email::new(&config).unwrap()
+    }
+
+    fn alert(role: &str, denom: &str, balance: &str) -> Alert {
+        Alert {
+            status: AlertStatus::Firing,
+            severity: "warning".to_string(),
+            chain_id: "mantra-1".to_string(),
+            role: role.to_string(),
+            address: "mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0".to_string(),
+            denom: denom.to_string(),
+            coin: CoinId {
+                coin_type: CoinType::COSMOS,
+                denom: format!("u{}", denom.to_lowercase()),
+                contract_address: None,
+            },
+            balance: balance.to_string(),
+            threshold: "10".to_string(),
+            balance_url: String::new(),
+            refresh: Duration::from_secs(120),
+        }
+    }
+
+    #[test]
+    fn digest_is_scheduled_daily() {
+        assert_eq!(
+            parse_time_of_day("08:30"),
+            Ok(Duration::from_secs(8 * 3600 + 30 * 60))
+        );
+        assert!(parse_time_of_day("24:00").is_err());
+        assert!(parse_time_of_day("8h").is_err());
+
+        let now = humantime::parse_rfc3339("2024-01-01T07:00:00Z").unwrap();
+        let at = parse_time_of_day("08:30").unwrap();
+        assert_eq!(until_time_of_day(now, at), Duration::from_secs(5400));
+        let now = humantime::parse_rfc3339("2024-01-01T08:30:00Z").unwrap();
+        assert_eq!(until_time_of_day(now, at), DAY);
+    }
+
+    #[test]
+    fn digest_lists_every_balance() {
+        let updated = humantime::parse_rfc3339("2024-01-01T08:30:00Z").unwrap();
+        let balances = [
+            (alert("faucet", "OM", "1200"), updated),
+            (alert("relayer", "OM", "9.5"), updated),
+        ];
+        let table = DigestTable(&balances).to_string();
+        let lines = table.lines().collect::<Vec<_>>();
+        assert!(lines[0].starts_with("CHAIN     ROLE"));
+        assert!(lines[0].ends_with("STATUS   UPDATED"));
+        assert!(lines[1].contains("faucet"));
+        assert!(lines[2].contains("relayer"));
+        assert!(lines[2].ends_with("9.5      10           warning  2024-01-01T08:30:00Z"));
+    }
+
+    #[tokio::test]
+    async fn mails_alerts_and_digests() {
+        let (port, mut received) = smtp_sink().await;
+        let email = email(port);
+
+        email.notify(&alert("relayer", "OM", "9.5")).await.unwrap();
+        let mail = received.recv().await.unwrap();
+        assert!(mail.contains("To: finance@example.com"), "{}", mail);
+        assert!(mail.contains("Subject: [firing] relayer on mantra-1 has 9.5 OM"));
+
+        email
+            .send_digest(&[(alert("relayer", "OM", "9.5"), SystemTime::now())])
+            .await
+            .unwrap();
+        let mail = received.recv().await.unwrap();
+        assert!(
+            mail.contains("Subject: Balance digest: 1 coins"),
+            "{}",
+            mail
+        );
+        assert!(mail.contains("mantra-1  relayer"));
+    }
+}
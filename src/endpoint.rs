@@ -0,0 +1,257 @@
+//! Endpoint failover
+use crate::config::{CoinType, EndpointSelection};
+use crate::error::{Error, QueryErrorClass};
+use log::warn;
+use std::future::Future;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+use tendermint_rpc::Url;
+
+/// The endpoints of one kind (gRPC or EVM) configured for a chain, shared by
+/// every address watched on it.
+#[derive(Debug)]
+pub struct EndpointPool {
+    kind: &'static str,
+    urls: Vec<String>,
+    selection: EndpointSelection,
+    timeout: Duration,
+    next: AtomicUsize,
+}
+
+impl EndpointPool {
+    pub fn new(
+        kind: &'static str,
+        urls: &[Url],
+        selection: EndpointSelection,
+        timeout: Duration,
+    ) -> Self {
+        Self {
+            kind,
+            urls: urls.iter().map(|url| url.to_string()).collect(),
+            selection,
+            timeout,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.urls.is_empty()
+    }
+
+    /// Endpoints in the order the next query should try them.
+    fn candidates(&self) -> impl Iterator<Item = &String> {
+        let start = match self.selection {
+            EndpointSelection::Priority => 0,
+            EndpointSelection::RoundRobin if !self.urls.is_empty() => {
+                self.next.fetch_add(1, Ordering::Relaxed) % self.urls.len()
+            }
+            EndpointSelection::RoundRobin => 0,
+        };
+        self.urls.iter().cycle().skip(start).take(self.urls.len())
+    }
+
+    /// Runs `query` for `denom` of `coin_type` against the endpoints until one
+    /// answers, failing over to the next endpoint on transport errors only,
+    /// including an endpoint not answering within the timeout. Returns the
+    /// result with the endpoint that produced it.
+    pub async fn execute<T, F, Fut>(
+        &self,
+        coin_type: &CoinType,
+        denom: &str,
+        query: F,
+    ) -> Result<(T, String), Error>
+    where
+        F: Fn(String) -> Fut,
+        Fut: Future<Output = anyhow::Result<T>>,
+    {
+        let query_error = |e: anyhow::Error, class: QueryErrorClass, url: &str| {
+            Error::query_error(
+                format!("{:#}", e),
+                url.to_string(),
+                coin_type.to_string(),
+                denom.to_string(),
+                class,
+            )
+        };
+        let mut last_error = None;
+        for url in self.candidates() {
+            let attempt = tokio::time::timeout(self.timeout, query(url.clone())).await;
+            match attempt {
+                Ok(Ok(value)) => return Ok((value, url.clone())),
+                Err(_) => {
+                    let e = anyhow::anyhow!("no answer within {:?}", self.timeout);
+                    warn!("{} endpoint {} failed, trying next: {}", self.kind, url, e);
+                    last_error = Some(query_error(e, QueryErrorClass::Timeout, url));
+                }
+                Ok(Err(e)) => {
+                    let class = QueryErrorClass::of(&e);
+                    if !class.is_transport() {
+                        return Err(query_error(e, class, url));
+                    }
+                    warn!("{} endpoint {} failed, trying next: {}", self.kind, url, e);
+                    last_error = Some(query_error(e, class, url));
+                }
+            }
+        }
+        Err(last_error.unwrap_or_else(|| {
+            Error::query_error(
+                format!("no {} endpoint configured", self.kind),
+                String::new(),
+                coin_type.to_string(),
+                denom.to_string(),
+                QueryErrorClass::NoEndpoint,
+            )
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::ErrorDetail;
+    use std::sync::Mutex;
+
+    fn pool(selection: EndpointSelection) -> EndpointPool {
+        let urls = ["https://a.example.com", "https://b.example.com"]
+            .iter()
+            .map(|url| url.parse::<Url>().unwrap())
+            .collect::<Vec<_>>();
+        EndpointPool::new("gRPC", &urls, selection, Duration::from_millis(100))
+    }
+
+    #[tokio::test]
+    async fn fails_over_on_transport_error() {
+        let pool = pool(EndpointSelection::Priority);
+        let tried = Mutex::new(Vec::new());
+        let (value, endpoint) = pool
+            .execute(&CoinType::COSMOS, "uom", |url| {
+                tried.lock().unwrap().push(url.clone());
+                async move {
+                    if url.contains("a.example") {
+                        Err(tonic::Status::unavailable("connection refused").into())
+                    } else {
+                        Ok(1)
+                    }
+                }
+            })
+            .await
+            .unwrap();
+        assert_eq!(value, 1);
+        assert_eq!(endpoint, "https://b.example.com/");
+        assert_eq!(tried.lock().unwrap().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn fails_over_when_endpoint_hangs() {
+        let pool = pool(EndpointSelection::Priority);
+        let (value, endpoint) = pool
+            .execute(&CoinType::COSMOS, "uom", |url| async move {
+                if url.contains("a.example") {
+                    std::future::pending::<()>().await;
+                }
+                Ok(1)
+            })
+            .await
+            .unwrap();
+        assert_eq!(value, 1);
+        assert_eq!(endpoint, "https://b.example.com/");
+
+        let err = pool
+            .execute(&CoinType::COSMOS, "uom", |_| async {
+                std::future::pending::<anyhow::Result<()>>().await
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.detail(),
+            ErrorDetail::QueryError(e) if e.class == QueryErrorClass::Timeout
+        ));
+    }
+
+    #[tokio::test]
+    async fn does_not_fail_over_on_query_error() {
+        let pool = pool(EndpointSelection::Priority);
+        let err = pool
+            .execute(&CoinType::CW20, "TEST", |_| async {
+                Err::<(), _>(tonic::Status::not_found("no contract").into())
+            })
+            .await
+            .unwrap_err();
+        match err.detail() {
+            ErrorDetail::QueryError(e) => {
+                assert_eq!(e.endpoint, "https://a.example.com/");
+                assert_eq!(e.class, QueryErrorClass::NotFound);
+                assert_eq!(e.denom, "TEST");
+            }
+            detail => panic!("unexpected error {:?}", detail),
+        }
+    }
+
+    #[tokio::test]
+    async fn contract_error_is_not_retried_on_other_endpoints() {
+        let pool = pool(EndpointSelection::Priority);
+        let tried = Mutex::new(Vec::new());
+        let err = pool
+            .execute(&CoinType::CW20, "TEST", |url| {
+                tried.lock().unwrap().push(url);
+                async {
+                    Err::<(), _>(
+                        tonic::Status::unknown(
+                            "unknown variant `balance`: query wasm contract failed",
+                        )
+                        .into(),
+                    )
+                }
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(*tried.lock().unwrap(), ["https://a.example.com/"]);
+        assert!(matches!(
+            err.detail(),
+            ErrorDetail::QueryError(e) if e.class == QueryErrorClass::InvalidRequest
+        ));
+    }
+
+    #[tokio::test]
+    async fn round_robin_rotates_first_endpoint() {
+        let pool = pool(EndpointSelection::RoundRobin);
+        let mut used = Vec::new();
+        for _ in 0..3 {
+            let (_, endpoint) = pool
+                .execute(&CoinType::COSMOS, "uom", |_| async { Ok(()) })
+                .await
+                .unwrap();
+            used.push(endpoint);
+        }
+        assert_eq!(
+            used,
+            [
+                "https://a.example.com/",
+                "https://b.example.com/",
+                "https://a.example.com/"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn empty_pool_is_an_error() {
+        let pool = EndpointPool::new(
+            "EVM",
+            &[],
+            EndpointSelection::Priority,
+            Duration::from_secs(1),
+        );
+        let err = pool
+            .execute(&CoinType::EVM, "wei", |_| async { Ok(()) })
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.detail(),
+            ErrorDetail::QueryError(e) if e.class == QueryErrorClass::NoEndpoint
+        ));
+    }
+}
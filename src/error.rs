@@ -1,5 +1,8 @@
+use crate::config::ConfigProblems;
 use flex_error::{define_error, TraceError};
+use std::fmt;
 use tonic::transport::Error as TransportError;
+use tonic::Code;
 
 define_error! {
     Error {
@@ -15,23 +18,59 @@ define_error! {
             [ TraceError<toml::ser::Error> ]
             |_| { "invalid configuration" },
 
-        ConfigParseU128
-            [ TraceError<std::num::ParseIntError> ]
-            |_| { "invalid number" },
+        AmountParse
+            { value: String }
+            |e| { format_args!(
+                "invalid amount: {}", e.value)
+            },
 
-        ConfigDecimalExceed
-            { decimal: u32 }
+        AmountPrecision
+            { value: String, decimal_place: u32 }
             |e| { format_args!(
-                "Decimals must not exceed 18: {}", e.decimal)
+                "amount {} has more than {} decimal places", e.value, e.decimal_place)
             },
 
-        ConfigMissingCW20ContractAddress
-            |_| {"Missing CW20 contract address"},
+        ConfigInvalid
+            { problems: ConfigProblems }
+            |e| { format_args!(
+                "invalid configuration:{}", e.problems)
+            },
 
         QueryError
-            { source: String, endpoint: String }
+            {
+                source: String,
+                endpoint: String,
+                coin_type: String,
+                denom: String,
+                class: QueryErrorClass,
+            }
+            |e| { format_args!(
+                "{} error querying {} {}: {} (endpoint: {})",
+                e.class, e.coin_type, e.denom, e.source, e.endpoint)
+            },
+
+        UnknownChain
+            { chain_id: String }
+            |e| { format_args!(
+                "chain {} is not in the config", e.chain_id)
+            },
+
+        UnknownCoinType
+            { coin_type: String }
             |e| { format_args!(
-                "{} (endpoint: {})", e.source, e.endpoint)
+                "no balance source registered for coin type {}", e.coin_type)
+            },
+
+        InvalidAddress
+            { reason: String }
+            |e| { format_args!(
+                "invalid address: {}", e.reason)
+            },
+
+        Notify
+            { notifier: String, reason: String }
+            |e| { format_args!(
+                "error notifying {}: {}", e.notifier, e.reason)
             },
 
         GrpcTransport
@@ -45,3 +84,235 @@ define_error! {
             },
     }
 }
+
+/// What went wrong with a balance query, used to tell an unreachable node
+/// apart from a misconfigured coin.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum QueryErrorClass {
+    /// The endpoint did not answer in time.
+    Timeout,
+    /// The endpoint could not be reached.
+    Unavailable,
+    /// The TLS handshake with the endpoint failed.
+    Tls,
+    /// The endpoint throttled the query.
+    RateLimited,
+    /// The queried account, denom or contract does not exist.
+    NotFound,
+    /// The endpoint answered with something that could not be decoded.
+    Decode,
+    /// The endpoint rejected the query, e.g. a malformed address.
+    InvalidRequest,
+    /// No endpoint is configured for the coin type.
+    NoEndpoint,
+    Other,
+}
+
+impl QueryErrorClass {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            QueryErrorClass::Timeout => "timeout",
+            QueryErrorClass::Unavailable => "unavailable",
+            QueryErrorClass::Tls => "tls",
+            QueryErrorClass::RateLimited => "rate_limited",
+            QueryErrorClass::NotFound => "not_found",
+            QueryErrorClass::Decode => "decode",
+            QueryErrorClass::InvalidRequest => "invalid_request",
+            QueryErrorClass::NoEndpoint => "no_endpoint",
+            QueryErrorClass::Other => "other",
+        }
+    }
+
+    /// Whether the endpoint itself is at fault, so another endpoint may succeed.
+    pub fn is_transport(&self) -> bool {
+        matches!(
+            self,
+            QueryErrorClass::Timeout
+                | QueryErrorClass::Unavailable
+                | QueryErrorClass::Tls
+                | QueryErrorClass::RateLimited
+        )
+    }
+
+    /// Classifies an error raised while querying an endpoint.
+    pub fn of(err: &anyhow::Error) -> Self {
+        if let Some(ErrorDetail::QueryError(e)) = err.downcast_ref::<Error>().map(Error::detail) {
+            return e.class;
+        }
+        let typed = err.chain().find_map(Self::of_cause);
+        let message = err
+            .chain()
+            .find_map(|cause| Self::of_message(&cause.to_string()));
+        match (typed, message) {
+            (
+                Some(QueryErrorClass::Unavailable)
+                | Some(QueryErrorClass::InvalidRequest)
+                | Some(QueryErrorClass::Other)
+                | None,
+                Some(message),
+            ) => message,
+            (Some(typed), _) => typed,
+            (None, None) => QueryErrorClass::Other,
+        }
+    }
+
+    fn of_cause(cause: &(dyn std::error::Error + 'static)) -> Option<Self> {
+        if let Some(status) = cause.downcast_ref::<tonic::Status>() {
+            return Some(match status.code() {
+                Code::DeadlineExceeded => QueryErrorClass::Timeout,
+                Code::NotFound => QueryErrorClass::NotFound,
+                Code::ResourceExhausted => QueryErrorClass::RateLimited,
+                Code::Unavailable => QueryErrorClass::Unavailable,
+                // tonic reports a broken connection as unknown too
+                Code::Unknown if status.message().contains("transport error") => {
+                    QueryErrorClass::Unavailable
+                }
+                // module errors without a registered gRPC code, e.g. a contract
+                // that is not a CW20
+                Code::Unknown => QueryErrorClass::InvalidRequest,
+                Code::InvalidArgument | Code::FailedPrecondition | Code::OutOfRange => {
+                    QueryErrorClass::InvalidRequest
+                }
+                _ => QueryErrorClass::Other,
+            });
+        }
+        if let Some(e) = cause.downcast_ref::<web3::Error>() {
+            return Some(match e {
+                web3::Error::Unreachable => QueryErrorClass::Unavailable,
+                web3::Error::Transport(web3::error::TransportError::Code(429)) => {
+                    QueryErrorClass::RateLimited
+                }
+                web3::Error::Transport(_) | web3::Error::Io(_) => QueryErrorClass::Unavailable,
+                web3::Error::Decoder(_) | web3::Error::InvalidResponse(_) => {
+                    QueryErrorClass::Decode
+                }
+                web3::Error::Rpc(_) => QueryErrorClass::InvalidRequest,
+                _ => QueryErrorClass::Other,
+            });
+        }
+        if cause.is::<web3::ethabi::Error>()
+            || cause.is::<serde_json::Error>()
+            || matches!(
+                cause.downcast_ref::<web3::contract::Error>(),
+                Some(web3::contract::Error::InvalidOutputType(_))
+            )
+        {
+            return Some(QueryErrorClass::Decode);
+        }
+        if cause.is::<http::uri::InvalidUri>() {
+            return Some(QueryErrorClass::InvalidRequest);
+        }
+        if cause.is::<TransportError>() {
+            return Some(QueryErrorClass::Unavailable);
+        }
+        if let Some(e) = cause.downcast_ref::<Error>() {
+            return match e.detail() {
+                ErrorDetail::GrpcTransport(_) => Some(QueryErrorClass::Unavailable),
+                ErrorDetail::AmountParse(_) => Some(QueryErrorClass::Decode),
+                ErrorDetail::GetCosmosBalance(_) => Some(QueryErrorClass::NotFound),
+                _ => None,
+            };
+        }
+        None
+    }
+
+    fn of_message(message: &str) -> Option<Self> {
+        let message = message.to_lowercase();
+        let contains_any = |patterns: &[&str]| patterns.iter().any(|p| message.contains(p));
+        if contains_any(&["timed out", "timeout", "deadline"]) {
+            Some(QueryErrorClass::Timeout)
+        } else if contains_any(&["certificate", "tls", "handshake", "ssl"]) {
+            Some(QueryErrorClass::Tls)
+        } else if contains_any(&["429", "too many requests", "rate limit"]) {
+            Some(QueryErrorClass::RateLimited)
+        } else if contains_any(&["not found", "no such contract"]) {
+            Some(QueryErrorClass::NotFound)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for QueryErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify_grpc_status() {
+        let class = |status: tonic::Status| QueryErrorClass::of(&status.into());
+        assert_eq!(
+            class(tonic::Status::deadline_exceeded("slow")),
+            QueryErrorClass::Timeout
+        );
+        assert_eq!(
+            class(tonic::Status::unavailable("connection refused")),
+            QueryErrorClass::Unavailable
+        );
+        assert_eq!(
+            class(tonic::Status::resource_exhausted("quota")),
+            QueryErrorClass::RateLimited
+        );
+        assert_eq!(
+            class(tonic::Status::invalid_argument(
+                "query wasm contract failed: contract: not found"
+            )),
+            QueryErrorClass::NotFound
+        );
+        assert_eq!(
+            class(tonic::Status::unavailable("invalid peer certificate")),
+            QueryErrorClass::Tls
+        );
+        let contract_error = class(tonic::Status::unknown(
+            "Error parsing into type cw20::query::QueryMsg: unknown variant `balance`: query wasm contract failed",
+        ));
+        assert_eq!(contract_error, QueryErrorClass::InvalidRequest);
+        assert!(!contract_error.is_transport());
+        assert_eq!(
+            class(tonic::Status::unknown("transport error")),
+            QueryErrorClass::Unavailable
+        );
+    }
+
+    #[test]
+    fn classify_evm_errors() {
+        let class = |e: web3::Error| QueryErrorClass::of(&e.into());
+        assert_eq!(
+            class(web3::Error::Transport(web3::error::TransportError::Code(
+                429
+            ))),
+            QueryErrorClass::RateLimited
+        );
+        assert_eq!(
+            class(web3::Error::Unreachable),
+            QueryErrorClass::Unavailable
+        );
+        assert_eq!(
+            class(web3::Error::Decoder("bad hex".to_string())),
+            QueryErrorClass::Decode
+        );
+        assert_eq!(
+            class(web3::Error::Transport(
+                web3::error::TransportError::Message("operation timed out".to_string())
+            )),
+            QueryErrorClass::Timeout
+        );
+    }
+
+    #[test]
+    fn classify_keeps_class_of_query_errors() {
+        let err = Error::query_error(
+            "boom".to_string(),
+            "https://grpc.example.com".to_string(),
+            "CW20".to_string(),
+            "TEST".to_string(),
+            QueryErrorClass::Decode,
+        );
+        assert_eq!(QueryErrorClass::of(&err.into()), QueryErrorClass::Decode);
+    }
+}
@@ -0,0 +1,108 @@
+//! gRPC channels shared across watched addresses
+use crate::error::{Error, QueryErrorClass};
+use crate::telemetry::{GRPC_CHANNEL_CONNECTS_COLLECTOR, GRPC_CHANNEL_EVICTIONS_COLLECTOR};
+use http::uri::Uri;
+use log::{debug, warn};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tonic::transport::{Channel, ClientTlsConfig, Endpoint};
+
+/// Builds a TLS-enabled endpoint for the given gRPC address.
+pub fn grpc_endpoint(grpc_addr: Uri) -> Result<Endpoint, Error> {
+    let tls_config = ClientTlsConfig::new().with_native_roots();
+    Channel::builder(grpc_addr)
+        .tls_config(tls_config)
+        .map_err(Error::grpc_transport)
+}
+
+/// One lazily connected channel per gRPC address, shared by every
+/// `track_account_status` task. A channel is dropped from the cache when a
+/// query over it fails to reach the node and is rebuilt on next use.
+#[derive(Clone, Default)]
+pub struct ChannelCache {
+    channels: Arc<Mutex<HashMap<String, Channel>>>,
+}
+
+impl ChannelCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the cached channel for `grpc_addr`, creating it if needed.
+    pub fn channel(&self, grpc_addr: &str) -> anyhow::Result<Channel> {
+        let mut channels = self.channels.lock().unwrap();
+        if let Some(channel) = channels.get(grpc_addr) {
+            return Ok(channel.clone());
+        }
+        let channel = grpc_endpoint(grpc_addr.parse::<Uri>()?)?.connect_lazy();
+        debug!("created gRPC channel to {}", grpc_addr);
+        GRPC_CHANNEL_CONNECTS_COLLECTOR
+            .with_label_values(&[grpc_addr])
+            .inc();
+        channels.insert(grpc_addr.to_string(), channel.clone());
+        Ok(channel)
+    }
+
+    /// Drops the channel for `grpc_addr` if `err` means the connection to the
+    /// node failed. Errors answered over a working connection, including rate
+    /// limits and module errors, keep it.
+    pub fn evict_on_error(&self, grpc_addr: &str, err: &anyhow::Error) {
+        if !matches!(
+            QueryErrorClass::of(err),
+            QueryErrorClass::Unavailable | QueryErrorClass::Tls
+        ) {
+            return;
+        }
+        if self.channels.lock().unwrap().remove(grpc_addr).is_some() {
+            warn!("dropped gRPC channel to {}: {}", grpc_addr, err);
+            GRPC_CHANNEL_EVICTIONS_COLLECTOR
+                .with_label_values(&[grpc_addr])
+                .inc();
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.channels.lock().unwrap().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn reuses_channel_per_endpoint() {
+        let cache = ChannelCache::new();
+        cache.channel("https://grpc.example.com").unwrap();
+        cache.channel("https://grpc.example.com").unwrap();
+        cache.channel("https://grpc.example.org").unwrap();
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn evicts_only_on_connection_error() {
+        let cache = ChannelCache::new();
+        let grpc_addr = "https://grpc.example.com";
+        cache.channel(grpc_addr).unwrap();
+
+        let not_found = anyhow::Error::new(tonic::Status::not_found("no such contract"));
+        cache.evict_on_error(grpc_addr, &not_found);
+        assert_eq!(cache.len(), 1);
+        let contract_error = anyhow::Error::new(tonic::Status::unknown(
+            "unknown variant `balance`: query wasm contract failed",
+        ));
+        cache.evict_on_error(grpc_addr, &contract_error);
+        assert_eq!(cache.len(), 1);
+        let rate_limited = anyhow::Error::new(tonic::Status::resource_exhausted("quota"));
+        cache.evict_on_error(grpc_addr, &rate_limited);
+        assert_eq!(cache.len(), 1);
+
+        let unavailable = anyhow::Error::new(tonic::Status::unavailable("connection refused"));
+        cache.evict_on_error(grpc_addr, &unavailable);
+        assert!(cache.is_empty());
+    }
+}
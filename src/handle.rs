@@ -1,35 +1,312 @@
+use crate::alert_state::{AlertState, AlertTracker};
+use crate::amount::Amount;
 use crate::config;
+use crate::email::{parse_time_of_day, until_time_of_day, Email};
+use crate::error::{ErrorDetail, QueryErrorClass};
+use crate::notify::{Alert, AlertStatus, CoinId, Notifiers};
+use crate::reload::ReloadTrigger;
+use crate::silence::Silences;
+use crate::source::{complete_balances, ChainEndpoints, SourceRegistry};
+use crate::spend::SpendWindow;
 use crate::telemetry::{
-    account_balance_setter, account_query_status_setter, account_status_setter,
-    ACCOUNT_BALANCE_COLLECTOR, ACCOUNT_QUERY_STATUS_COLLECTOR, ACCOUNT_STATUS_COLLECTOR,
+    account_alert_state_setter, account_balance_setter, account_ceiling_status_setter,
+    account_critical_status_setter, account_denom_query_status_setter, account_query_errors_inc,
+    account_query_status_setter, account_silenced_setter, account_spend_rate_setter,
+    account_status_setter, account_time_to_min_balance_setter, expire_account_series,
+    remove_account_series, reset_account_series,
 };
-use cosmos_sdk_proto::cosmos::base::v1beta1::Coin;
 use log::{error, info, warn};
-use std::collections::HashMap;
-use tendermint_rpc::Url;
-
-pub async fn account_status_collector(config: config::Config) {
-    for chain_config in config.chains.iter() {
-        let grpc_addr = chain_config.grpc_addr.clone();
-        let evm_addr = chain_config.evm_addr.clone();
-        let chain_id = chain_config.id.clone();
-        for chain_address in chain_config.addresses.clone().iter() {
-            tokio::task::spawn(track_account_status(
-                grpc_addr.clone(),
-                evm_addr.clone(),
-                chain_id.clone(),
-                chain_address.clone(),
-            ));
-        }
-    }
-    if let Some(interval) = config.prometheus.reset {
-        let mut reset_interval = tokio::time::interval(interval);
-        loop {
-            reset_interval.tick().await;
-            info!("reset metrics!");
-            ACCOUNT_BALANCE_COLLECTOR.reset();
-            ACCOUNT_STATUS_COLLECTOR.reset();
-            ACCOUNT_QUERY_STATUS_COLLECTOR.reset();
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use tokio::task::JoinHandle;
+use web3::types::U256;
+
+/// What `track_account_status` keeps of one coin across refreshes.
+#[derive(Clone)]
+struct CoinState {
+    spend: SpendWindow,
+    alert: AlertTracker,
+}
+
+/// Watch every address of `config` and apply the config at `config_path`
+/// again on SIGHUP, or when the file changes if `watch` is set. A config that
+/// fails to load leaves the running one in place.
+pub async fn account_status_collector(
+    config_path: PathBuf,
+    config: config::Config,
+    registry: Arc<SourceRegistry>,
+    silences: Arc<Silences>,
+    watch: Option<Duration>,
+) {
+    let coin_types = registry.coin_types();
+    let mut supervisor = Supervisor::new(registry, silences);
+    supervisor.apply(&config).await;
+    let mut trigger = match ReloadTrigger::new(&config_path, watch) {
+        Ok(trigger) => trigger,
+        Err(e) => {
+            error!("config reload is disabled: {}", e);
+            return std::future::pending().await;
+        }
+    };
+    let mut running = config;
+    loop {
+        trigger.changed().await;
+        info!("reloading config file: {}", config_path.display());
+        match config::load_with(&config_path, &coin_types) {
+            Ok(config) => {
+                if config.prometheus.host != running.prometheus.host
+                    || config.prometheus.port != running.prometheus.port
+                {
+                    warn!("prometheus host and port changes take effect after a restart");
+                }
+                if config.api != running.api {
+                    warn!("api changes take effect after a restart");
+                }
+                supervisor.apply(&config).await;
+                running = config;
+            }
+            Err(e) => error!("keeping the running config: {}", e),
+        }
+    }
+}
+
+/// Identifies the task watching one address.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct TaskKey {
+    pub chain_id: String,
+    pub address: String,
+    pub role: String,
+}
+
+/// Tasks started, restarted and stopped by [`Supervisor::apply`].
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct ConfigDiff {
+    pub started: Vec<TaskKey>,
+    pub restarted: Vec<TaskKey>,
+    pub stopped: Vec<TaskKey>,
+}
+
+struct AddressTask {
+    chain_config: config::ChainConfig,
+    chain_address: config::Address,
+    series_ttl: Option<Duration>,
+    handle: JoinHandle<()>,
+}
+
+/// Runs one `track_account_status` task per configured address and keeps
+/// them in line with the config, touching only the addresses that changed.
+pub struct Supervisor {
+    registry: Arc<SourceRegistry>,
+    notifiers: Arc<Notifiers>,
+    /// Endpoints shared by the tasks of each chain, with the chain config
+    /// (without its addresses) they were built from.
+    chains: HashMap<String, (config::ChainConfig, ChainEndpoints)>,
+    tasks: HashMap<TaskKey, AddressTask>,
+    reset: Option<(Duration, JoinHandle<()>)>,
+    /// The task mailing the daily digest, with the mail server it sends to.
+    digest: Option<(config::SmtpConfig, JoinHandle<()>)>,
+}
+
+impl Supervisor {
+    pub fn new(registry: Arc<SourceRegistry>, silences: Arc<Silences>) -> Self {
+        Self {
+            registry,
+            notifiers: Arc::new(Notifiers::new(silences)),
+            chains: HashMap::new(),
+            tasks: HashMap::new(),
+            reset: None,
+            digest: None,
+        }
+    }
+
+    /// Start the tasks of new addresses, restart those whose address or chain
+    /// settings changed and stop those no longer configured, removing their
+    /// gauge series.
+    pub async fn apply(&mut self, config: &config::Config) -> ConfigDiff {
+        self.notifiers.configure(config);
+        let mut diff = ConfigDiff::default();
+        let mut chains = HashMap::new();
+        let mut configured = HashSet::new();
+        for chain_config in config.chains.iter() {
+            let chain_settings = config::ChainConfig {
+                addresses: Vec::new(),
+                ..chain_config.clone()
+            };
+            let endpoints = match self.chains.remove(&chain_config.id) {
+                Some((settings, endpoints)) if settings == chain_settings => endpoints,
+                _ => ChainEndpoints::from_config(chain_config),
+            };
+            for chain_address in chain_config.addresses.iter() {
+                let series_ttl = config.prometheus.series_ttl(chain_address.refresh);
+                let key = TaskKey {
+                    chain_id: chain_config.id.clone(),
+                    address: chain_address.label().to_string(),
+                    role: chain_address.role.clone(),
+                };
+                if !configured.insert(key.clone()) {
+                    warn!(
+                        "address ({}) for {} on ({}) is configured twice, watching the first one",
+                        key.address, key.role, key.chain_id
+                    );
+                    continue;
+                }
+                match self.tasks.get(&key) {
+                    Some(task)
+                        if task.chain_config == chain_settings
+                            && &task.chain_address == chain_address
+                            && task.series_ttl == series_ttl =>
+                    {
+                        continue
+                    }
+                    Some(_) => {
+                        self.stop(&key).await;
+                        // the digest no longer lists the coins dropped from the address
+                        let watched = chain_address
+                            .coins
+                            .iter()
+                            .map(CoinId::from)
+                            .collect::<Vec<_>>();
+                        self.notifiers
+                            .forget(&key.chain_id, &key.address, &key.role, &watched);
+                        diff.restarted.push(key.clone());
+                    }
+                    None => diff.started.push(key.clone()),
+                }
+                let handle = tokio::task::spawn(track_account_status(
+                    self.registry.clone(),
+                    self.notifiers.clone(),
+                    endpoints.clone(),
+                    chain_config.id.clone(),
+                    chain_config.bech32_prefix.clone(),
+                    chain_address.clone(),
+                    series_ttl,
+                ));
+                self.tasks.insert(
+                    key,
+                    AddressTask {
+                        chain_config: chain_settings.clone(),
+                        chain_address: chain_address.clone(),
+                        series_ttl,
+                        handle,
+                    },
+                );
+            }
+            chains.insert(chain_config.id.clone(), (chain_settings, endpoints));
+        }
+        self.chains = chains;
+
+        let removed = self
+            .tasks
+            .keys()
+            .filter(|key| !configured.contains(*key))
+            .cloned()
+            .collect::<Vec<_>>();
+        for key in removed {
+            self.stop(&key).await;
+            self.notifiers
+                .forget(&key.chain_id, &key.address, &key.role, &[]);
+            diff.stopped.push(key);
+        }
+        self.apply_reset(config.prometheus.reset);
+        self.apply_digest(config.smtp.as_ref());
+
+        info!(
+            "watching {} addresses: {} started, {} restarted, {} stopped",
+            self.tasks.len(),
+            diff.started.len(),
+            diff.restarted.len(),
+            diff.stopped.len()
+        );
+        diff
+    }
+
+    pub fn len(&self) -> usize {
+        self.tasks.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tasks.is_empty()
+    }
+
+    /// Abort the task of `key` and remove the series it set, once it has
+    /// ended so it cannot set them again.
+    async fn stop(&mut self, key: &TaskKey) {
+        if let Some(task) = self.tasks.remove(key) {
+            task.handle.abort();
+            let _ = task.handle.await;
+            remove_account_series(&key.chain_id, &key.address, &key.role);
+        }
+    }
+
+    fn apply_reset(&mut self, interval: Option<Duration>) {
+        if self.reset.as_ref().map(|(current, _)| *current) == interval {
+            return;
+        }
+        if let Some((_, handle)) = self.reset.take() {
+            handle.abort();
+        }
+        self.reset = interval.map(|interval| {
+            let handle = tokio::task::spawn(async move {
+                let mut reset_interval = tokio::time::interval(interval);
+                // the first tick completes immediately
+                reset_interval.tick().await;
+                loop {
+                    reset_interval.tick().await;
+                    info!("reset metrics!");
+                    reset_account_series();
+                }
+            });
+            (interval, handle)
+        });
+    }
+
+    fn apply_digest(&mut self, smtp: Option<&config::SmtpConfig>) {
+        if self.digest.as_ref().map(|(current, _)| current) == smtp {
+            return;
+        }
+        if let Some((_, handle)) = self.digest.take() {
+            handle.abort();
+        }
+        let Some(smtp) = smtp else { return };
+        let Some(digest_at) = &smtp.digest_at else {
+            return;
+        };
+        let digest = parse_time_of_day(digest_at)
+            .and_then(|at| Ok((at, Email::new(smtp).map_err(|e| e.to_string())?)));
+        let (at, email) = match digest {
+            Ok(digest) => digest,
+            Err(e) => {
+                error!("the balance digest is disabled: {}", e);
+                return;
+            }
+        };
+        let notifiers = self.notifiers.clone();
+        let handle = tokio::task::spawn(async move {
+            loop {
+                tokio::time::sleep(until_time_of_day(SystemTime::now(), at)).await;
+                let balances = notifiers.snapshot();
+                match email.send_digest(&balances).await {
+                    Ok(()) => info!("mailed the digest of {} balances", balances.len()),
+                    Err(e) => error!("error mailing the balance digest: {}", e),
+                }
+            }
+        });
+        self.digest = Some((smtp.clone(), handle));
+    }
+}
+
+impl Drop for Supervisor {
+    fn drop(&mut self) {
+        for task in self.tasks.values() {
+            task.handle.abort();
+        }
+        if let Some((_, handle)) = &self.reset {
+            handle.abort();
+        }
+        if let Some((_, handle)) = &self.digest {
+            handle.abort();
         }
     }
 }
@@ -39,132 +316,357 @@ pub struct CoinEntity {
     pub coin_type: config::CoinType,
     pub contract_address: Option<String>,
     pub decimal_place: u32,
+    pub display_precision: Option<u32>,
     pub denom: String,
     pub display_denom: String,
     pub display_min_balance: String,
-    pub min_balance: String,
+    pub min_balance: U256,
+    /// Empty when the coin has no critical threshold.
+    pub display_critical_balance: String,
+    pub critical_balance: Option<U256>,
+    /// Empty when the coin has no ceiling.
+    pub display_max_balance: String,
+    pub max_balance: Option<U256>,
+    /// A firing alert resolves above this balance, min_balance when unset.
+    pub clear_balance: U256,
+    /// How long min_balance must be breached before the alert fires.
+    pub for_duration: Duration,
 }
 
-pub async fn track_account_status(
-    grpc_addr: Option<Url>,
-    evm_addr: Option<Url>,
-    chain_id: String,
-    chain_address: config::Address,
-) {
-    let address = chain_address
-        .hex_address
-        .unwrap_or(chain_address.address.clone());
-    let refresh = &chain_address.refresh;
-    let balance_url = &chain_address.balance_url;
-    let role = &chain_address.role;
-    let mut collect_interval = tokio::time::interval(refresh.to_owned());
-    let mut coin_map: HashMap<config::CoinType, Vec<CoinEntity>> = HashMap::new();
+impl CoinEntity {
+    /// Severity of `amount` against the thresholds of the coin.
+    pub fn status(&self, amount: U256) -> BalanceStatus {
+        match self.critical_balance {
+            Some(critical_balance) if amount <= critical_balance => BalanceStatus::Critical,
+            _ if amount <= self.min_balance => BalanceStatus::Warning,
+            _ => BalanceStatus::Ok,
+        }
+    }
+
+    /// Whether `amount` is above the ceiling of the coin, if it has one.
+    pub fn above_max(&self, amount: U256) -> Option<bool> {
+        self.max_balance.map(|max_balance| amount > max_balance)
+    }
+}
+
+/// Severity of a balance, exported as `account_status`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum BalanceStatus {
+    /// Above `min_balance`.
+    Ok,
+    /// At or below `min_balance`.
+    Warning,
+    /// At or below `critical_balance`.
+    Critical,
+}
+
+impl BalanceStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BalanceStatus::Ok => "ok",
+            BalanceStatus::Warning => "warning",
+            BalanceStatus::Critical => "critical",
+        }
+    }
+
+    pub fn as_i64(&self) -> i64 {
+        *self as i64
+    }
+}
+
+/// The coins of `chain_address` grouped by coin type, in the order the coin
+/// types first appear in the config.
+pub fn coin_groups(chain_address: &config::Address) -> Vec<(config::CoinType, Vec<CoinEntity>)> {
+    let mut groups: Vec<(config::CoinType, Vec<CoinEntity>)> = Vec::new();
     for coin in chain_address.coins.iter() {
-        let display_min_balance = from_atomics(&coin.min_balance, coin.decimal_place);
+        // thresholds are checked by config::load
+        let min_balance = coin.parse_threshold(&coin.min_balance).unwrap();
+        let display_min_balance = Amount::new(min_balance, coin.decimal_place)
+            .with_precision(coin.display_precision)
+            .to_string();
+        let critical_balance = coin
+            .critical_balance
+            .as_ref()
+            .map(|critical_balance| coin.parse_threshold(critical_balance).unwrap());
+        let display_critical_balance = critical_balance
+            .map(|critical_balance| {
+                Amount::new(critical_balance, coin.decimal_place)
+                    .with_precision(coin.display_precision)
+                    .to_string()
+            })
+            .unwrap_or_default();
+        let max_balance = coin
+            .max_balance
+            .as_ref()
+            .map(|max_balance| coin.parse_threshold(max_balance).unwrap());
+        let display_max_balance = max_balance
+            .map(|max_balance| {
+                Amount::new(max_balance, coin.decimal_place)
+                    .with_precision(coin.display_precision)
+                    .to_string()
+            })
+            .unwrap_or_default();
+        let clear_balance = coin
+            .clear_balance
+            .as_ref()
+            .map_or(min_balance, |clear_balance| {
+                coin.parse_threshold(clear_balance).unwrap()
+            });
         let coin_entity = CoinEntity {
             coin_type: coin.coin_type.clone(),
             contract_address: coin.contract_address.clone(),
             decimal_place: coin.decimal_place,
+            display_precision: coin.display_precision,
             denom: coin.denom.clone(),
             display_denom: coin.display_denom.clone().unwrap_or(coin.denom.clone()),
             display_min_balance,
-            min_balance: coin.min_balance.clone(),
+            min_balance,
+            display_critical_balance,
+            critical_balance,
+            display_max_balance,
+            max_balance,
+            clear_balance,
+            for_duration: coin.for_duration.unwrap_or_default(),
         };
-        coin_map
-            .entry(coin.coin_type.clone())
-            .or_default()
-            .push(coin_entity);
+        match groups
+            .iter_mut()
+            .find(|(coin_type, _)| *coin_type == coin.coin_type)
+        {
+            Some((_, coin_entities)) => coin_entities.push(coin_entity),
+            None => groups.push((coin.coin_type.clone(), vec![coin_entity])),
+        }
+    }
+    groups
+}
+
+pub async fn track_account_status(
+    registry: Arc<SourceRegistry>,
+    notifiers: Arc<Notifiers>,
+    endpoints: ChainEndpoints,
+    chain_id: String,
+    bech32_prefix: Option<String>,
+    chain_address: config::Address,
+    series_ttl: Option<Duration>,
+) {
+    // metrics are labelled with the configured address whichever form is queried
+    let address = chain_address.label().to_string();
+    let refresh = &chain_address.refresh;
+    let balance_url = &chain_address.balance_url;
+    let role = &chain_address.role;
+    let mut collect_interval = tokio::time::interval(refresh.to_owned());
+    let coin_map = coin_groups(&chain_address);
+    let coin_state = CoinState {
+        spend: SpendWindow::new(chain_address.spend_window),
+        alert: AlertTracker::default(),
+    };
+    let mut coin_states = coin_map
+        .iter()
+        .map(|(_, coin_entities)| vec![coin_state.clone(); coin_entities.len()])
+        .collect::<Vec<_>>();
+    let mut query_addresses: HashMap<config::CoinType, String> = HashMap::new();
+    for (coin_type, _) in coin_map.iter() {
+        // the address forms are checked by config::load
+        match chain_address.query_address(coin_type, bech32_prefix.as_deref()) {
+            Ok(query_address) => {
+                query_addresses.insert(coin_type.clone(), query_address);
+            }
+            Err(e) => error!(
+                "cannot query {} coins with address ({}) for {} on ({}): {}",
+                coin_type, address, role, chain_id, e
+            ),
+        }
     }
 
     loop {
         collect_interval.tick().await;
-        for (coin_type, coin_entities) in coin_map.iter() {
-            let tmp_balances = match coin_type
-                .get_balances(
-                    address.clone(),
-                    coin_entities,
-                    grpc_addr.clone(),
-                    evm_addr.clone(),
-                )
-                .await
+        for ((coin_type, coin_entities), coin_states) in coin_map.iter().zip(coin_states.iter_mut())
+        {
+            let Some(query_address) = query_addresses.get(coin_type) else {
+                continue;
+            };
+            let Some(source) = registry.get(coin_type) else {
+                error!(
+                    "no balance source registered for coin type {} with address ({}) for {} on ({})",
+                    coin_type, address, role, chain_id
+                );
+                continue;
+            };
+            let results = source
+                .get_balances(query_address.clone(), coin_entities, &endpoints)
+                .await;
+            let results = complete_balances(coin_entities, results);
+            // the address is reported unreachable as soon as one of its coins is
+            let mut query_status = (0, String::new());
+            for ((coin_entity, result), coin_state) in coin_entities
+                .iter()
+                .zip(results)
+                .zip(coin_states.iter_mut())
             {
-                Ok((balances, query_endpoint_url)) => {
-                    account_query_status_setter(
+                let (coin, query_endpoint_url) = match result {
+                    Ok(balance) => balance,
+                    Err(e) => {
+                        error!("{} and retry next refresh", e);
+                        let (query_endpoint_url, error_class) = match e.detail() {
+                            ErrorDetail::QueryError(e) => (e.endpoint.clone(), e.class),
+                            _ => (String::new(), QueryErrorClass::Other),
+                        };
+                        account_query_errors_inc(
+                            &chain_id,
+                            &address,
+                            role,
+                            coin_type.as_str(),
+                            error_class.as_str(),
+                            &query_endpoint_url,
+                        );
+                        account_denom_query_status_setter(
+                            &chain_id,
+                            &address,
+                            &coin_entity.display_denom,
+                            role,
+                            balance_url.as_ref().unwrap_or(&"".to_string()),
+                            &query_endpoint_url,
+                            1,
+                        );
+                        if query_status.0 == 0 {
+                            query_status = (1, query_endpoint_url);
+                        }
+                        continue;
+                    }
+                };
+                account_denom_query_status_setter(
+                    &chain_id,
+                    &address,
+                    &coin_entity.display_denom,
+                    role,
+                    balance_url.as_ref().unwrap_or(&"".to_string()),
+                    &query_endpoint_url,
+                    0,
+                );
+                if query_status.0 == 0 {
+                    query_status.1 = query_endpoint_url;
+                }
+
+                let status = coin_entity.status(coin.amount);
+                match status {
+                    BalanceStatus::Ok => {}
+                    BalanceStatus::Warning => warn!("The current balance {}{denom} is less than {}{denom} with address ({}) for {} on ({})", coin.amount, coin_entity.min_balance, address, role, chain_id, denom=coin.denom),
+                    BalanceStatus::Critical => error!("The current balance {}{denom} is less than the critical {}{denom} with address ({}) for {} on ({})", coin.amount, coin_entity.critical_balance.unwrap_or_default(), address, role, chain_id, denom=coin.denom),
+                }
+                let display_balance = Amount::new(coin.amount, coin_entity.decimal_place)
+                    .with_precision(coin_entity.display_precision);
+                let now = Instant::now();
+                let alert_state = coin_state.alert.update(coin_entity, coin.amount, now);
+                account_alert_state_setter(
+                    &chain_id,
+                    &address,
+                    &coin_entity.display_denom,
+                    &coin_entity.display_min_balance,
+                    role,
+                    balance_url.as_ref().unwrap_or(&"".to_string()),
+                    alert_state.as_i64(),
+                );
+                let alert = Alert {
+                    status: match alert_state {
+                        AlertState::Pending | AlertState::Firing => AlertStatus::Firing,
+                        AlertState::Ok | AlertState::Resolved => AlertStatus::Resolved,
+                    },
+                    severity: status.as_str().to_string(),
+                    chain_id: chain_id.clone(),
+                    role: role.clone(),
+                    address: address.clone(),
+                    denom: coin_entity.display_denom.clone(),
+                    coin: CoinId {
+                        coin_type: coin_entity.coin_type.clone(),
+                        denom: coin_entity.denom.clone(),
+                        contract_address: coin_entity.contract_address.clone(),
+                    },
+                    balance: display_balance.to_string(),
+                    threshold: coin_entity.display_min_balance.clone(),
+                    balance_url: balance_url.clone().unwrap_or_default(),
+                    refresh: chain_address.refresh,
+                };
+                notifiers.record(&alert);
+                account_silenced_setter(
+                    &chain_id,
+                    &address,
+                    &coin_entity.display_denom,
+                    role,
+                    balance_url.as_ref().unwrap_or(&"".to_string()),
+                    i64::from(notifiers.is_silenced(&alert)),
+                );
+                // a pending alert leaves the notifiers where they were
+                if alert_state != AlertState::Pending {
+                    notifiers.observe(alert);
+                }
+                account_status_setter(
+                    &chain_id,
+                    &address,
+                    &coin_entity.display_denom,
+                    &coin_entity.display_min_balance,
+                    role,
+                    balance_url.as_ref().unwrap_or(&"".to_string()),
+                    status.as_i64(),
+                );
+                if coin_entity.critical_balance.is_some() {
+                    account_critical_status_setter(
                         &chain_id,
                         &address,
+                        &coin_entity.display_denom,
+                        &coin_entity.display_critical_balance,
                         role,
                         balance_url.as_ref().unwrap_or(&"".to_string()),
-                        &query_endpoint_url,
-                        0,
+                        i64::from(status == BalanceStatus::Critical),
                     );
-                    balances
                 }
-                Err(e) => {
-                    error!("{} and retry next refresh", e);
-                    let error_string = e.to_string();
-                    let query_endpoint_url = error_string
-                        .split(" (endpoint: ")
-                        .last()
-                        .unwrap_or("")
-                        .split(')')
-                        .next()
-                        .unwrap_or("");
-                    account_query_status_setter(
+                if let Some(above_max) = coin_entity.above_max(coin.amount) {
+                    if above_max {
+                        warn!("The current balance {}{denom} is more than {}{denom} with address ({}) for {} on ({})", coin.amount, coin_entity.max_balance.unwrap_or_default(), address, role, chain_id, denom=coin.denom);
+                    }
+                    account_ceiling_status_setter(
                         &chain_id,
                         &address,
+                        &coin_entity.display_denom,
+                        &coin_entity.display_max_balance,
                         role,
                         balance_url.as_ref().unwrap_or(&"".to_string()),
-                        query_endpoint_url,
-                        1,
+                        above_max as i64,
                     );
-                    continue;
                 }
-            };
 
-            for coin_entity in coin_entities {
-                let default_coin = Coin {
-                    denom: coin_entity.denom.clone(),
-                    amount: "0".to_owned(),
-                };
-                let coin = tmp_balances
-                    .iter()
-                    .find(|coin| coin.denom == coin_entity.denom)
-                    .unwrap_or(&default_coin);
-                if coin.amount.parse::<u128>().unwrap()
-                    <= coin_entity.min_balance.parse::<u128>().unwrap()
+                coin_state.spend.record(now, coin.amount);
+                if let Some(per_hour) = coin_state.spend.outflow_per_hour(coin_entity.decimal_place)
                 {
-                    warn!("The current balance {}{denom} is less than {}{denom} with address ({}) for {} on ({})", coin.amount, coin_entity.min_balance, address, role, chain_id, denom=coin.denom);
-
-                    account_status_setter(
+                    account_spend_rate_setter(
                         &chain_id,
                         &address,
                         &coin_entity.display_denom,
-                        &coin_entity.display_min_balance,
                         role,
                         balance_url.as_ref().unwrap_or(&"".to_string()),
-                        1,
+                        per_hour,
                     );
-                } else {
-                    account_status_setter(
+                    let time_to_min_balance = coin_state
+                        .spend
+                        .time_to(coin_entity.min_balance, coin_entity.decimal_place)
+                        .map_or(f64::INFINITY, |time_to| time_to.as_secs_f64());
+                    account_time_to_min_balance_setter(
                         &chain_id,
                         &address,
                         &coin_entity.display_denom,
                         &coin_entity.display_min_balance,
                         role,
                         balance_url.as_ref().unwrap_or(&"".to_string()),
-                        0,
+                        time_to_min_balance,
                     );
                 }
 
                 if chain_address.disable_balance != Some(true) {
-                    let display_balance = from_atomics(&coin.amount, coin_entity.decimal_place);
                     account_balance_setter(
                         &chain_id,
                         &address,
                         &coin_entity.display_denom,
                         role,
                         balance_url.as_ref().unwrap_or(&"".to_string()),
-                        display_balance.parse::<i64>().unwrap(),
+                        display_balance.to_f64(),
                     );
                 }
                 info!(
@@ -172,17 +674,148 @@ pub async fn track_account_status(
                     coin.amount, coin.denom, address, role, chain_id
                 );
             }
+            account_query_status_setter(
+                &chain_id,
+                &address,
+                role,
+                balance_url.as_ref().unwrap_or(&"".to_string()),
+                &query_status.1,
+                query_status.0,
+            );
+        }
+        if let Some(ttl) = series_ttl {
+            let expired = expire_account_series(&chain_id, &address, role, ttl);
+            if expired > 0 {
+                info!(
+                    "removed {} stale series of address ({}) for {} on ({})",
+                    expired, address, role, chain_id
+                );
+            }
         }
     }
 }
 
-fn from_atomics(number: &str, decimal_place: u32) -> String {
-    let base = 10u128;
-    let divisor = base.checked_pow(decimal_place).unwrap();
-    number
-        .parse::<u128>()
-        .unwrap()
-        .checked_div(divisor)
-        .unwrap_or_default()
-        .to_string()
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(toml: &str) -> config::Config {
+        toml::from_str(toml).unwrap()
+    }
+
+    const CHAIN_A: &str = r#"
+        [[chains]]
+        id = 'chain_A'
+        grpc_addr = 'http://127.0.0.1:9090'
+        [[chains.addresses]]
+        address = 'mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0'
+        role = 'relayer'
+        [[chains.addresses.coins]]
+        denom = 'uom'
+        min_balance = '1'
+        [[chains.addresses]]
+        address = 'mantra1x5nk33zpglp4ge6q9a8xx3zceqf4g8nvaggjmc'
+        role = 'faucet'
+        [[chains.addresses.coins]]
+        denom = 'uom'
+        min_balance = '1'
+        "#;
+
+    fn key(address: &str, role: &str) -> TaskKey {
+        TaskKey {
+            chain_id: "chain_A".to_string(),
+            address: address.to_string(),
+            role: role.to_string(),
+        }
+    }
+
+    #[test]
+    fn status_follows_thresholds() {
+        let chain_address = config(
+            r#"
+            [[chains]]
+            id = 'chain_A'
+            [[chains.addresses]]
+            address = 'someone'
+            role = 'relayer'
+            [[chains.addresses.coins]]
+            denom = 'uom'
+            display_denom = 'OM'
+            min_balance = '1000000'
+            critical_balance = '0.5 OM'
+            [[chains.addresses.coins]]
+            coin_type = 'EVM'
+            denom = 'wei'
+            min_balance = '10'
+            max_balance = '100'
+            "#,
+        )
+        .chains[0]
+            .addresses[0]
+            .clone();
+        let groups = coin_groups(&chain_address);
+        let uom = &groups[0].1[0];
+        assert_eq!(uom.display_critical_balance, "0.5");
+        assert_eq!(uom.status(U256::from(1_000_001)), BalanceStatus::Ok);
+        assert_eq!(uom.status(U256::from(1_000_000)), BalanceStatus::Warning);
+        assert_eq!(uom.status(U256::from(500_000)), BalanceStatus::Critical);
+        assert_eq!(BalanceStatus::Critical.as_i64(), 2);
+
+        let wei = &groups[1].1[0];
+        assert_eq!(wei.display_critical_balance, "");
+        assert_eq!(wei.status(U256::zero()), BalanceStatus::Warning);
+        assert_eq!(wei.above_max(U256::from(100)), Some(false));
+        assert_eq!(wei.above_max(U256::from(101)), Some(true));
+        assert_eq!(uom.above_max(U256::MAX), None);
+    }
+
+    #[tokio::test]
+    async fn reload_touches_only_changed_addresses() {
+        let mut supervisor = Supervisor::new(
+            Arc::new(SourceRegistry::empty()),
+            Arc::new(Silences::default()),
+        );
+        let diff = supervisor.apply(&config(CHAIN_A)).await;
+        assert_eq!(diff.started.len(), 2);
+        assert_eq!(supervisor.len(), 2);
+
+        assert_eq!(
+            supervisor.apply(&config(CHAIN_A)).await,
+            ConfigDiff::default()
+        );
+
+        let changed = CHAIN_A
+            .replacen("min_balance = '1'", "min_balance = '2'", 1)
+            .replace(
+                "mantra1x5nk33zpglp4ge6q9a8xx3zceqf4g8nvaggjmc",
+                "mantra1wrvwhcfuhqe7eru59ehkxxr2e262ksnzhtfmdtr96wctr8m2kafq2vh64r",
+            );
+        let diff = supervisor.apply(&config(&changed)).await;
+        assert_eq!(
+            diff,
+            ConfigDiff {
+                started: vec![key(
+                    "mantra1wrvwhcfuhqe7eru59ehkxxr2e262ksnzhtfmdtr96wctr8m2kafq2vh64r",
+                    "faucet"
+                )],
+                restarted: vec![key(
+                    "mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0",
+                    "relayer"
+                )],
+                stopped: vec![key(
+                    "mantra1x5nk33zpglp4ge6q9a8xx3zceqf4g8nvaggjmc",
+                    "faucet"
+                )],
+            }
+        );
+
+        // a chain setting restarts every address of the chain
+        let diff = supervisor
+            .apply(&config(&changed.replace("9090", "9091")))
+            .await;
+        assert_eq!(diff.restarted.len(), 2);
+
+        supervisor.apply(&config::Config::default()).await;
+        assert!(supervisor.is_empty());
+    }
 }
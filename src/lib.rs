@@ -6,10 +6,24 @@
     unused_qualifications
 )]
 
+pub mod address;
+pub mod alert_state;
+pub mod amount;
+pub mod check;
 pub mod config;
+pub mod discover;
+pub mod email;
+pub mod endpoint;
 pub mod error;
+pub mod grpc;
 pub mod handle;
+pub mod lint;
+pub mod notify;
 pub mod query;
+pub mod reload;
+pub mod silence;
+pub mod source;
+pub mod spend;
 pub mod telemetry;
 
 pub const DEFAULT_CONFIG_PATH: &str = "chains.toml";
@@ -0,0 +1,215 @@
+//! Config diagnostics with file line numbers
+use crate::config::{validate, Config, ConfigProblem, ConfigTable};
+use crate::grpc::grpc_endpoint;
+use futures::future::join_all;
+use http::uri::Uri;
+use std::fmt;
+use std::time::Duration;
+use toml_edit::ImDocument;
+
+/// A problem found in a config file, with the line of the entry it was found in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// 1-based line number, when the problem can be located in the file.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "line {}: {}", line, self.message),
+            None => f.write_str(&self.message),
+        }
+    }
+}
+
+/// Parse and validate `config_toml`, reporting every problem found. A file
+/// that does not match the config schema is reported as a single diagnostic
+/// since decoding stops at the first mismatch.
+pub fn lint(config_toml: &str) -> Result<Config, Vec<Diagnostic>> {
+    let config = toml::from_str::<Config>(config_toml).map_err(|e| {
+        vec![Diagnostic {
+            line: e.span().map(|span| line_of(config_toml, span.start)),
+            message: e.message().to_string(),
+        }]
+    })?;
+    let problems = validate(&config);
+    if problems.is_empty() {
+        Ok(config)
+    } else {
+        Err(locate(config_toml, &problems))
+    }
+}
+
+/// Diagnostics for `problems` of the config parsed from `config_toml`.
+pub fn locate(config_toml: &str, problems: &[ConfigProblem]) -> Vec<Diagnostic> {
+    let document = ImDocument::parse(config_toml).ok();
+    problems
+        .iter()
+        .map(|problem| Diagnostic {
+            line: document
+                .as_ref()
+                .and_then(|document| entry_offset(document, problem))
+                .map(|offset| line_of(config_toml, offset)),
+            message: problem.to_string(),
+        })
+        .collect()
+}
+
+/// Offset of the table `problem` was found in.
+fn entry_offset(document: &ImDocument<&str>, problem: &ConfigProblem) -> Option<usize> {
+    let entry = match problem.table {
+        ConfigTable::Chain(index) => document.get("chains")?.as_array_of_tables()?.get(index)?,
+        ConfigTable::Webhook(index) => {
+            document.get("webhooks")?.as_array_of_tables()?.get(index)?
+        }
+        ConfigTable::Alertmanager => document.get("alertmanager")?.as_table()?,
+        ConfigTable::Smtp => document.get("smtp")?.as_table()?,
+        ConfigTable::Silence(index) => {
+            document.get("silences")?.as_array_of_tables()?.get(index)?
+        }
+    };
+    let table = match problem.address {
+        None => entry,
+        Some(address) => {
+            let address = entry.get("addresses")?.as_array_of_tables()?.get(address)?;
+            match problem.coin {
+                None => address,
+                Some(coin) => address.get("coins")?.as_array_of_tables()?.get(coin)?,
+            }
+        }
+    };
+    table.span().map(|span| span.start)
+}
+
+fn line_of(config_toml: &str, offset: usize) -> usize {
+    config_toml[..offset.min(config_toml.len())]
+        .matches('\n')
+        .count()
+        + 1
+}
+
+/// Try to reach every gRPC and EVM endpoint of `config` within `timeout`,
+/// reporting one problem per endpoint that could not be reached.
+pub async fn check_endpoints(config: &Config, timeout: Duration) -> Vec<ConfigProblem> {
+    let mut probes = Vec::new();
+    for (chain_index, chain_config) in config.chains.iter().enumerate() {
+        let endpoints = chain_config
+            .grpc_addr
+            .iter()
+            .map(|url| (true, url))
+            .chain(chain_config.evm_addr.iter().map(|url| (false, url)));
+        for (is_grpc, url) in endpoints {
+            let context = format!("chain '{}'", chain_config.id);
+            probes.push(async move {
+                probe(is_grpc, url.to_string(), timeout)
+                    .await
+                    .map(|message| ConfigProblem {
+                        table: ConfigTable::Chain(chain_index),
+                        address: None,
+                        coin: None,
+                        context,
+                        message,
+                    })
+            });
+        }
+    }
+    join_all(probes).await.into_iter().flatten().collect()
+}
+
+/// Why the gRPC (or else EVM) endpoint `url` could not be reached, if it could not.
+async fn probe(is_grpc: bool, url: String, timeout: Duration) -> Option<String> {
+    let result = if is_grpc {
+        async {
+            grpc_endpoint(url.parse::<Uri>()?)?
+                .connect_timeout(timeout)
+                .connect()
+                .await?;
+            anyhow::Ok(())
+        }
+        .await
+    } else {
+        async {
+            let web3 = web3::Web3::new(web3::transports::Http::new(&url)?);
+            tokio::time::timeout(timeout, web3.eth().block_number()).await??;
+            anyhow::Ok(())
+        }
+        .await
+    };
+    let kind = if is_grpc { "gRPC" } else { "EVM" };
+    result
+        .err()
+        .map(|e| format!("{} endpoint {} is unreachable: {:#}", kind, url, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"[prometheus]
+host = '127.0.0.1'
+port = 9090
+
+[[chains]]
+id = 'chain_A'
+grpc_addr = 'http://127.0.0.1:9090'
+
+[[chains.addresses]]
+address = 'mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0'
+role = 'relayer'
+
+[[chains.addresses.coins]]
+denom = 'uom'
+min_balance = '1'
+
+[[chains.addresses.coins]]
+denom = 'uusdc'
+min_balance = 'ten'
+
+[[chains.addresses]]
+address = 'mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0'
+role = 'relayer'
+
+[[chains]]
+id = 'chain_A'
+"#;
+
+    #[test]
+    fn reports_problems_with_line_numbers() {
+        let diagnostics = lint(CONFIG).unwrap_err();
+        let lines = diagnostics
+            .iter()
+            .map(|diagnostic| diagnostic.line)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, [Some(17), Some(21), Some(25)], "{:#?}", diagnostics);
+        assert!(diagnostics[0].message.contains("min_balance 'ten'"));
+        assert!(diagnostics[1].message.contains("more than once"));
+        assert!(diagnostics[2].message.contains("chain id"));
+        assert!(diagnostics[0].to_string().starts_with("line 17: "));
+    }
+
+    #[test]
+    fn reports_schema_error_line() {
+        let config = CONFIG.replace(
+            "role = 'relayer'\n\n[[chains.addresses.coins]]",
+            "rol = 'relayer'\n\n[[chains.addresses.coins]]",
+        );
+        let diagnostics = lint(&config).unwrap_err();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].line.is_some());
+        assert!(diagnostics[0].message.contains("rol"), "{:?}", diagnostics);
+    }
+
+    #[tokio::test]
+    async fn reports_unreachable_endpoints() {
+        let config = toml::from_str::<Config>(
+            "[[chains]]\nid = 'chain_A'\ngrpc_addr = 'http://127.0.0.1:1'\nevm_addr = 'http://127.0.0.1:1'\n",
+        )
+        .unwrap();
+        let problems = check_endpoints(&config, Duration::from_secs(2)).await;
+        assert_eq!(problems.len(), 2, "{:#?}", problems);
+        assert!(problems[0].message.contains("gRPC endpoint"));
+        assert!(problems[1].message.contains("EVM endpoint"));
+    }
+}
@@ -0,0 +1,858 @@
+//! Notifications on `account_status` transitions
+use crate::config::{self, AlertmanagerConfig, Config, WebhookConfig};
+use crate::email::Email;
+use crate::error::Error;
+use crate::silence::Silences;
+use async_trait::async_trait;
+use log::{error, info, warn};
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Whether a coin of a watched address is at or below its min_balance.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertStatus {
+    Firing,
+    Resolved,
+}
+
+impl AlertStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Firing => "firing",
+            Self::Resolved => "resolved",
+        }
+    }
+}
+
+/// A coin of a watched address crossing its min_balance, in either direction.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct Alert {
+    pub status: AlertStatus,
+    /// `account_status` of the coin: ok, warning or critical.
+    pub severity: String,
+    pub chain_id: String,
+    pub role: String,
+    pub address: String,
+    /// Display denom of the coin.
+    pub denom: String,
+    #[serde(skip)]
+    pub coin: CoinId,
+    /// Balance and threshold in display units.
+    pub balance: String,
+    pub threshold: String,
+    pub balance_url: String,
+    /// How often the balance of the coin is queried.
+    #[serde(skip)]
+    pub refresh: Duration,
+}
+
+/// A coin of an address as configured, unique within the address where its
+/// display denom may not be.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct CoinId {
+    pub coin_type: config::CoinType,
+    pub denom: String,
+    pub contract_address: Option<String>,
+}
+
+impl From<&config::Coin> for CoinId {
+    fn from(coin: &config::Coin) -> Self {
+        Self {
+            coin_type: coin.coin_type.clone(),
+            denom: coin.denom.clone(),
+            contract_address: coin.contract_address.clone(),
+        }
+    }
+}
+
+/// The `chain_id`, `address`, `role` and coin of an alert.
+type AlertKey = (String, String, String, CoinId);
+
+impl Alert {
+    fn key(&self) -> AlertKey {
+        (
+            self.chain_id.clone(),
+            self.address.clone(),
+            self.role.clone(),
+            self.coin.clone(),
+        )
+    }
+
+    /// The values of the template placeholders.
+    fn fields(&self) -> [(&'static str, &str); 9] {
+        [
+            ("status", self.status.as_str()),
+            ("severity", &self.severity),
+            ("chain_id", &self.chain_id),
+            ("role", &self.role),
+            ("address", &self.address),
+            ("denom", &self.denom),
+            ("balance", &self.balance),
+            ("threshold", &self.threshold),
+            ("balance_url", &self.balance_url),
+        ]
+    }
+
+    fn example() -> Self {
+        Self {
+            status: AlertStatus::Firing,
+            severity: "warning".to_string(),
+            chain_id: "mantra-1".to_string(),
+            role: "relayer".to_string(),
+            address: "mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0".to_string(),
+            denom: "OM".to_string(),
+            coin: CoinId {
+                coin_type: config::CoinType::COSMOS,
+                denom: "uom".to_string(),
+                contract_address: None,
+            },
+            balance: "9.5".to_string(),
+            threshold: "10".to_string(),
+            balance_url: "https://www.mintscan.io/mantra".to_string(),
+            refresh: Duration::from_secs(120),
+        }
+    }
+}
+
+/// Fill the `{{field}}` placeholders of `template` with the fields of `alert`,
+/// escaped to be placed within JSON strings. Unknown placeholders are kept.
+pub fn render(template: &str, alert: &Alert) -> String {
+    let fields = alert.fields();
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}").map(|end| start + end) else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        let name = rest[start + 2..end].trim();
+        match fields.iter().find(|(field, _)| *field == name) {
+            Some((_, value)) => {
+                let quoted = serde_json::Value::from(*value).to_string();
+                rendered.push_str(&quoted[1..quoted.len() - 1]);
+            }
+            None => rendered.push_str(&rest[start..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Why `template` cannot be used as a webhook body, if it cannot.
+pub fn check_template(template: &str) -> Result<(), String> {
+    let example = Alert::example();
+    let unknown = render(template, &example).match_indices("{{").count();
+    if unknown > 0 {
+        return Err(format!(
+            "template has {} unknown placeholders, expected any of {}",
+            unknown,
+            example
+                .fields()
+                .map(|(field, _)| format!("{{{{{}}}}}", field))
+                .join(", ")
+        ));
+    }
+    serde_json::from_str::<serde_json::Value>(&render(template, &example))
+        .map(|_| ())
+        .map_err(|e| format!("template does not render to JSON: {}", e))
+}
+
+/// Delivers alerts to one destination.
+#[async_trait]
+pub trait Notifier: Send + Sync {
+    /// Send `alert` once, retries are up to the caller.
+    async fn notify(&self, alert: &Alert) -> Result<(), Error>;
+}
+
+/// Posts alerts as JSON to a URL.
+pub struct Webhook {
+    url: String,
+    template: Option<String>,
+    client: reqwest::Client,
+}
+
+impl Webhook {
+    pub fn new(config: &WebhookConfig) -> Result<Self, Error> {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| Error::notify(config.name.clone(), e.to_string()))?;
+        Ok(Self {
+            url: config.url.clone(),
+            template: config.template.clone(),
+            client,
+        })
+    }
+
+    /// The body posted for `alert`.
+    pub fn payload(&self, alert: &Alert) -> String {
+        match &self.template {
+            Some(template) => render(template, alert),
+            // an Alert always serializes
+            None => serde_json::to_string(alert).unwrap_or_default(),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for Webhook {
+    async fn notify(&self, alert: &Alert) -> Result<(), Error> {
+        self.client
+            .post(&self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(self.payload(alert))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| Error::notify(self.url.clone(), e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// Pushes alerts to the v2 API of an Alertmanager. Firing alerts end after
+/// a few resend intervals unless posted again, resolved ones end now.
+pub struct Alertmanager {
+    url: String,
+    labels: BTreeMap<String, String>,
+    resend_interval: Duration,
+    client: reqwest::Client,
+}
+
+impl Alertmanager {
+    pub fn new(config: &AlertmanagerConfig) -> Result<Self, Error> {
+        let client = reqwest::Client::builder()
+            .timeout(config.timeout)
+            .build()
+            .map_err(|e| Error::notify(config.url.clone(), e.to_string()))?;
+        Ok(Self {
+            url: format!("{}/api/v2/alerts", config.url.trim_end_matches('/')),
+            labels: config.labels.clone(),
+            resend_interval: config.resend_interval,
+            client,
+        })
+    }
+
+    /// The body posted for `alert` at `now`. A firing alert ends after 4
+    /// missed resends, which happen no more often than the coin is queried.
+    pub fn payload(&self, alert: &Alert, now: SystemTime) -> serde_json::Value {
+        let mut labels = self.labels.clone();
+        labels.insert("alertname".to_string(), "AccountBalanceLow".to_string());
+        labels.insert("chain_id".to_string(), alert.chain_id.clone());
+        labels.insert("address".to_string(), alert.address.clone());
+        labels.insert("role".to_string(), alert.role.clone());
+        labels.insert("denom".to_string(), alert.denom.clone());
+        let ends_at = match alert.status {
+            AlertStatus::Firing => now + self.resend_interval.max(alert.refresh) * 4,
+            AlertStatus::Resolved => now,
+        };
+        serde_json::json!([{
+            "labels": labels,
+            "annotations": {
+                "summary": format!(
+                    "{} {} has {} {}, min_balance is {}",
+                    alert.role, alert.address, alert.balance, alert.denom, alert.threshold
+                ),
+                // not a label: it changes while the alert fires and resolves
+                // with `ok`, which would make it another alert
+                "severity": alert.severity,
+                "balance": alert.balance,
+                "threshold": alert.threshold,
+                "balance_url": alert.balance_url,
+            },
+            "startsAt": humantime::format_rfc3339_seconds(now).to_string(),
+            "endsAt": humantime::format_rfc3339_seconds(ends_at).to_string(),
+        }])
+    }
+}
+
+#[async_trait]
+impl Notifier for Alertmanager {
+    async fn notify(&self, alert: &Alert) -> Result<(), Error> {
+        self.client
+            .post(&self.url)
+            .json(&self.payload(alert, SystemTime::now()))
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .map_err(|e| Error::notify(self.url.clone(), e.to_string()))?;
+        Ok(())
+    }
+}
+
+/// How often a failed notification is attempted again.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RetryPolicy {
+    pub retries: u32,
+    pub interval: Duration,
+}
+
+/// Send `alert` with `notifier`, attempting again as `retry` allows.
+pub async fn deliver(
+    notifier: &dyn Notifier,
+    alert: &Alert,
+    retry: RetryPolicy,
+) -> Result<(), Error> {
+    let mut attempt = 0;
+    loop {
+        match notifier.notify(alert).await {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < retry.retries => {
+                attempt += 1;
+                warn!(
+                    "{}, attempt {} of {} in {:?}",
+                    e,
+                    attempt + 1,
+                    retry.retries + 1,
+                    retry.interval
+                );
+                tokio::time::sleep(retry.interval).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// How alerts are delivered to one notifier.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Delivery {
+    pub retry: RetryPolicy,
+    /// Also notify when the balance is back above min_balance.
+    pub send_resolved: bool,
+    /// Notify again this often while the alert is firing.
+    pub resend: Option<Duration>,
+}
+
+#[derive(Clone)]
+struct Route {
+    name: String,
+    notifier: Arc<dyn Notifier>,
+    delivery: Delivery,
+}
+
+/// The last status seen of one coin, and when each notifier that resends was
+/// last notified of it.
+struct AlertState {
+    status: AlertStatus,
+    sent: HashMap<String, Instant>,
+}
+
+/// Sends an alert to every notifier when a coin goes below its min_balance
+/// and when it recovers.
+#[derive(Default)]
+pub struct Notifiers {
+    routes: RwLock<Vec<Route>>,
+    states: Mutex<HashMap<AlertKey, AlertState>>,
+    /// The latest balance of every coin and when it was queried, for the digest.
+    latest: Mutex<HashMap<AlertKey, (Alert, SystemTime)>>,
+    silences: Arc<Silences>,
+}
+
+impl Notifiers {
+    pub fn new(silences: Arc<Silences>) -> Self {
+        Self {
+            silences,
+            ..Self::default()
+        }
+    }
+
+    /// Replace the notifiers with the webhooks, Alertmanager and mail server of
+    /// `config`, and the silences of the config.
+    /// The known status of every coin is kept so a reload does not notify again.
+    pub fn configure(&self, config: &Config) {
+        let mut routes = Vec::new();
+        for webhook in config.webhooks.iter() {
+            match Webhook::new(webhook) {
+                Ok(notifier) => routes.push(Route {
+                    name: webhook.name.clone(),
+                    notifier: Arc::new(notifier),
+                    delivery: Delivery {
+                        retry: RetryPolicy {
+                            retries: webhook.retries,
+                            interval: webhook.retry_interval,
+                        },
+                        send_resolved: webhook.send_resolved,
+                        resend: None,
+                    },
+                }),
+                Err(e) => error!("webhook {} is disabled: {}", webhook.name, e),
+            }
+        }
+        if let Some(alertmanager) = &config.alertmanager {
+            match Alertmanager::new(alertmanager) {
+                Ok(notifier) => routes.push(Route {
+                    name: "alertmanager".to_string(),
+                    notifier: Arc::new(notifier),
+                    delivery: Delivery {
+                        retry: RetryPolicy {
+                            retries: alertmanager.retries,
+                            interval: alertmanager.retry_interval,
+                        },
+                        send_resolved: true,
+                        resend: Some(alertmanager.resend_interval),
+                    },
+                }),
+                Err(e) => error!("alertmanager is disabled: {}", e),
+            }
+        }
+        if let Some(smtp) = &config.smtp {
+            match Email::new(smtp) {
+                Ok(notifier) => routes.push(Route {
+                    name: "smtp".to_string(),
+                    notifier: Arc::new(notifier),
+                    delivery: Delivery {
+                        retry: RetryPolicy {
+                            retries: smtp.retries,
+                            interval: smtp.retry_interval,
+                        },
+                        send_resolved: smtp.send_resolved,
+                        resend: None,
+                    },
+                }),
+                Err(e) => error!("smtp is disabled: {}", e),
+            }
+        }
+        *self.routes.write().unwrap() = routes;
+        self.silences.configure(&config.silences);
+    }
+
+    /// Add a notifier, e.g. one not built from the config, until the next
+    /// [`Notifiers::configure`].
+    pub fn push(&self, name: &str, notifier: Arc<dyn Notifier>, delivery: Delivery) {
+        self.routes.write().unwrap().push(Route {
+            name: name.to_string(),
+            notifier,
+            delivery,
+        });
+    }
+
+    /// Record the status of the coin of `alert` and notify in the background
+    /// when it changed, or when a notifier is due a resend of a firing alert.
+    /// A coin first seen below min_balance notifies too. Returns whether
+    /// `alert` was a transition. A silenced alert leaves the status as it was,
+    /// so a coin still firing once the silence ends notifies then.
+    pub fn observe(&self, alert: Alert) -> bool {
+        if self.is_silenced(&alert) {
+            return false;
+        }
+        let now = Instant::now();
+        let mut states = self.states.lock().unwrap();
+        let state = states.entry(alert.key()).or_insert_with(|| AlertState {
+            status: AlertStatus::Resolved,
+            sent: HashMap::new(),
+        });
+        let changed = state.status != alert.status;
+        state.status = alert.status;
+        let routes = self
+            .routes
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|route| match (alert.status, route.delivery.resend) {
+                (AlertStatus::Resolved, _) => changed && route.delivery.send_resolved,
+                (AlertStatus::Firing, _) if changed => true,
+                (AlertStatus::Firing, None) => false,
+                (AlertStatus::Firing, Some(resend)) => state
+                    .sent
+                    .get(&route.name)
+                    .is_none_or(|sent| now.duration_since(*sent) >= resend),
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        match alert.status {
+            AlertStatus::Firing => {
+                for route in routes
+                    .iter()
+                    .filter(|route| route.delivery.resend.is_some())
+                {
+                    state.sent.insert(route.name.clone(), now);
+                }
+            }
+            AlertStatus::Resolved => state.sent.clear(),
+        }
+        drop(states);
+        self.dispatch(alert, routes);
+        changed
+    }
+
+    /// Whether an active silence matches the coin of `alert`.
+    pub fn is_silenced(&self, alert: &Alert) -> bool {
+        self.silences.is_silenced(
+            &alert.chain_id,
+            &alert.address,
+            &alert.role,
+            &alert.denom,
+            SystemTime::now(),
+        )
+    }
+
+    /// Keep `alert` as the latest balance of its coin, whatever its status.
+    pub fn record(&self, alert: &Alert) {
+        self.latest
+            .lock()
+            .unwrap()
+            .insert(alert.key(), (alert.clone(), SystemTime::now()));
+    }
+
+    /// The latest balance of every watched coin and when it was queried, by
+    /// chain, role, address and denom.
+    pub fn snapshot(&self) -> Vec<(Alert, SystemTime)> {
+        let mut alerts = self
+            .latest
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect::<Vec<_>>();
+        alerts.sort_by(|(a, _), (b, _)| {
+            (&a.chain_id, &a.role, &a.address, &a.denom).cmp(&(
+                &b.chain_id,
+                &b.role,
+                &b.address,
+                &b.denom,
+            ))
+        });
+        alerts
+    }
+
+    /// Forget the coins of an address not in `watched`, every coin of an
+    /// address no longer watched.
+    pub fn forget(&self, chain_id: &str, address: &str, role: &str, watched: &[CoinId]) {
+        let kept = |(chain, watched_address, watched_role, coin): &AlertKey| {
+            (
+                chain.as_str(),
+                watched_address.as_str(),
+                watched_role.as_str(),
+            ) != (chain_id, address, role)
+                || watched.contains(coin)
+        };
+        self.states.lock().unwrap().retain(|key, _| kept(key));
+        self.latest.lock().unwrap().retain(|key, _| kept(key));
+    }
+
+    fn dispatch(&self, alert: Alert, routes: Vec<Route>) {
+        let alert = Arc::new(alert);
+        for route in routes {
+            let alert = alert.clone();
+            tokio::spawn(async move {
+                match deliver(route.notifier.as_ref(), &alert, route.delivery.retry).await {
+                    Ok(()) => info!(
+                        "notified {} of {} {} with address ({}) for {} on ({})",
+                        route.name,
+                        alert.status.as_str(),
+                        alert.denom,
+                        alert.address,
+                        alert.role,
+                        alert.chain_id
+                    ),
+                    Err(e) => error!("giving up notifying {}: {}", route.name, e),
+                }
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::mpsc;
+    use warp::Filter;
+
+    struct Recorder(mpsc::UnboundedSender<Alert>);
+
+    #[async_trait]
+    impl Notifier for Recorder {
+        async fn notify(&self, alert: &Alert) -> Result<(), Error> {
+            self.0.send(alert.clone()).unwrap();
+            Ok(())
+        }
+    }
+
+    fn alert(status: AlertStatus) -> Alert {
+        Alert {
+            status,
+            ..Alert::example()
+        }
+    }
+
+    #[test]
+    fn render_escapes_values() {
+        let alert = Alert {
+            role: "relayer \"A\"".to_string(),
+            ..Alert::example()
+        };
+        let rendered = render(
+            r#"{"text": "{{ status }}: {{role}} has {{balance}} {{denom}} {{unknown}}"}"#,
+            &alert,
+        );
+        assert_eq!(
+            rendered,
+            r#"{"text": "firing: relayer \"A\" has 9.5 OM {{unknown}}"}"#
+        );
+        assert!(check_template(r#"{"text": "{{role}}"}"#).is_ok());
+        assert!(check_template(r#"{"text": "{{rol}}"}"#)
+            .unwrap_err()
+            .contains("unknown placeholders"));
+        assert!(check_template(r#"{"text": {{role}}}"#)
+            .unwrap_err()
+            .contains("does not render to JSON"));
+    }
+
+    #[test]
+    fn snapshot_keeps_latest_balance_of_watched_coins() {
+        let notifiers = Notifiers::default();
+        let faucet = Alert {
+            role: "faucet".to_string(),
+            ..Alert::example()
+        };
+        notifiers.record(&alert(AlertStatus::Firing));
+        notifiers.record(&faucet);
+        notifiers.record(&Alert {
+            balance: "12".to_string(),
+            ..alert(AlertStatus::Resolved)
+        });
+        let snapshot = notifiers.snapshot();
+        assert_eq!(snapshot.len(), 2);
+        assert_eq!(snapshot[0].0, faucet);
+        assert_eq!(snapshot[1].0.balance, "12");
+        assert!(snapshot[1].1 >= snapshot[0].1);
+
+        // a restarted address keeps the coins it still watches
+        let usdc = CoinId {
+            denom: "uusdc".to_string(),
+            ..faucet.coin.clone()
+        };
+        notifiers.forget(
+            &faucet.chain_id,
+            &faucet.address,
+            "relayer",
+            std::slice::from_ref(&faucet.coin),
+        );
+        notifiers.forget(&faucet.chain_id, &faucet.address, "faucet", &[usdc]);
+        let snapshot = notifiers.snapshot();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(snapshot[0].0.role, "relayer");
+
+        notifiers.forget(&faucet.chain_id, &faucet.address, "relayer", &[]);
+        assert!(notifiers.snapshot().is_empty());
+    }
+
+    #[tokio::test]
+    async fn notifies_on_transitions() {
+        let notifiers = Notifiers::default();
+        let (sender, mut received) = mpsc::unbounded_channel();
+        let delivery = Delivery {
+            retry: RetryPolicy {
+                retries: 0,
+                interval: Duration::ZERO,
+            },
+            send_resolved: true,
+            resend: None,
+        };
+        notifiers.push("recorder", Arc::new(Recorder(sender)), delivery);
+
+        assert!(!notifiers.observe(alert(AlertStatus::Resolved)));
+        assert!(notifiers.observe(alert(AlertStatus::Firing)));
+        assert!(!notifiers.observe(alert(AlertStatus::Firing)));
+        assert!(notifiers.observe(alert(AlertStatus::Resolved)));
+        assert_eq!(received.recv().await.unwrap().status, AlertStatus::Firing);
+        assert_eq!(received.recv().await.unwrap().status, AlertStatus::Resolved);
+
+        notifiers.forget(
+            "mantra-1",
+            "mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0",
+            "relayer",
+            &[],
+        );
+        assert!(notifiers.observe(alert(AlertStatus::Firing)));
+    }
+
+    #[tokio::test]
+    async fn silenced_alerts_notify_once_the_silence_ends() {
+        let silences = Arc::new(Silences::default());
+        let notifiers = Notifiers::new(silences.clone());
+        let (sender, mut received) = mpsc::unbounded_channel();
+        let delivery = Delivery {
+            retry: RetryPolicy {
+                retries: 0,
+                interval: Duration::ZERO,
+            },
+            send_resolved: true,
+            resend: None,
+        };
+        notifiers.push("recorder", Arc::new(Recorder(sender)), delivery);
+
+        let config = toml::from_str::<Config>(
+            "[[silences]]\nrole = 'relayer'\nends_at = '2999-01-01T00:00:00Z'\n",
+        )
+        .unwrap();
+        silences.configure(&config.silences);
+        assert!(notifiers.is_silenced(&alert(AlertStatus::Firing)));
+        assert!(!notifiers.observe(alert(AlertStatus::Firing)));
+
+        silences.configure(&[]);
+        assert!(notifiers.observe(alert(AlertStatus::Firing)));
+        assert_eq!(received.recv().await.unwrap().status, AlertStatus::Firing);
+        assert!(received.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn alertmanager_receives_firing_and_resolved_alerts() {
+        let (sender, mut received) = mpsc::unbounded_channel();
+        let route = warp::post()
+            .and(warp::path!("api" / "v2" / "alerts"))
+            .and(warp::body::json())
+            .map(move |body: serde_json::Value| {
+                sender.send(body).unwrap();
+                warp::reply()
+            });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let config = toml::from_str::<Config>(&format!(
+            "[alertmanager]\nurl = 'http://{}/'\nresend_interval = '0s'\nlabels = {{ env = 'test' }}\n",
+            addr
+        ))
+        .unwrap();
+        let notifiers = Notifiers::default();
+        notifiers.configure(&config);
+        let ends_at = |body: &serde_json::Value| {
+            humantime::parse_rfc3339(body[0]["endsAt"].as_str().unwrap()).unwrap()
+        };
+
+        assert!(notifiers.observe(alert(AlertStatus::Firing)));
+        let firing = received.recv().await.unwrap();
+        assert_eq!(
+            firing[0]["labels"],
+            serde_json::json!({
+                "alertname": "AccountBalanceLow",
+                "chain_id": "mantra-1",
+                "address": "mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0",
+                "role": "relayer",
+                "denom": "OM",
+                "env": "test",
+            })
+        );
+        assert_eq!(
+            firing[0]["annotations"]["balance_url"],
+            "https://www.mintscan.io/mantra"
+        );
+        assert_eq!(firing[0]["annotations"]["severity"], "warning");
+        assert!(ends_at(&firing) >= SystemTime::now() - Duration::from_secs(1));
+
+        // still firing, posted again as the resend interval elapsed
+        assert!(!notifiers.observe(alert(AlertStatus::Firing)));
+        assert_eq!(
+            received.recv().await.unwrap()[0]["labels"],
+            firing[0]["labels"]
+        );
+
+        // turning critical is the same alert
+        assert!(!notifiers.observe(Alert {
+            severity: "critical".to_string(),
+            ..alert(AlertStatus::Firing)
+        }));
+        let critical = received.recv().await.unwrap();
+        assert_eq!(critical[0]["labels"], firing[0]["labels"]);
+        assert_eq!(critical[0]["annotations"]["severity"], "critical");
+
+        assert!(notifiers.observe(Alert {
+            severity: "ok".to_string(),
+            ..alert(AlertStatus::Resolved)
+        }));
+        let resolved = received.recv().await.unwrap();
+        assert!(ends_at(&resolved) <= SystemTime::now());
+        assert_eq!(resolved[0]["labels"], firing[0]["labels"]);
+    }
+
+    #[test]
+    fn firing_alerts_end_after_missed_resends() {
+        let config =
+            toml::from_str::<AlertmanagerConfig>("url = 'http://127.0.0.1:9093'\n").unwrap();
+        let alertmanager = Alertmanager::new(&config).unwrap();
+        assert_eq!(alertmanager.url, "http://127.0.0.1:9093/api/v2/alerts");
+        let now = humantime::parse_rfc3339("2024-01-01T00:00:00Z").unwrap();
+        let refreshed_every = |refresh: u64| Alert {
+            refresh: Duration::from_secs(refresh),
+            ..alert(AlertStatus::Firing)
+        };
+        let payload = alertmanager.payload(&refreshed_every(30), now);
+        assert_eq!(payload[0]["startsAt"], "2024-01-01T00:00:00Z");
+        assert_eq!(payload[0]["endsAt"], "2024-01-01T00:04:00Z");
+        // resent no more often than refreshed
+        let payload = alertmanager.payload(&refreshed_every(300), now);
+        assert_eq!(payload[0]["endsAt"], "2024-01-01T00:20:00Z");
+        let payload = alertmanager.payload(&alert(AlertStatus::Resolved), now);
+        assert_eq!(payload[0]["endsAt"], "2024-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn coins_sharing_a_display_denom_are_tracked_apart() {
+        let notifiers = Notifiers::default();
+        let bridged = Alert {
+            coin: CoinId {
+                coin_type: config::CoinType::EVM_ERC20,
+                denom: "om".to_string(),
+                contract_address: Some("0xAb5801a7D398351b8bE11C439e05C5B3259aeC9B".to_string()),
+            },
+            ..alert(AlertStatus::Resolved)
+        };
+        assert!(notifiers.observe(alert(AlertStatus::Firing)));
+        assert!(!notifiers.observe(bridged.clone()));
+        assert!(!notifiers.observe(alert(AlertStatus::Firing)));
+        notifiers.record(&alert(AlertStatus::Firing));
+        notifiers.record(&bridged);
+        assert_eq!(notifiers.snapshot().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn webhook_retries_until_accepted() {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let (sender, mut received) = mpsc::unbounded_channel();
+        let route = warp::post().and(warp::body::json()).map({
+            let attempts = attempts.clone();
+            move |body: serde_json::Value| {
+                if attempts.fetch_add(1, Ordering::SeqCst) == 0 {
+                    return warp::http::StatusCode::SERVICE_UNAVAILABLE;
+                }
+                sender.send(body).unwrap();
+                warp::http::StatusCode::OK
+            }
+        });
+        let (addr, server) = warp::serve(route).bind_ephemeral(([127, 0, 0, 1], 0));
+        tokio::spawn(server);
+
+        let config = toml::from_str::<WebhookConfig>(&format!(
+            "name = 'ops'\nurl = 'http://{}/hook'\ntemplate = '{{\"text\": \"{{{{denom}}}} below {{{{threshold}}}}\"}}'",
+            addr
+        ))
+        .unwrap();
+        let webhook = Webhook::new(&config).unwrap();
+        let retry = RetryPolicy {
+            retries: 1,
+            interval: Duration::from_millis(10),
+        };
+        deliver(&webhook, &alert(AlertStatus::Firing), retry)
+            .await
+            .unwrap();
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+        assert_eq!(
+            received.recv().await.unwrap(),
+            serde_json::json!({"text": "OM below 10"})
+        );
+
+        let retry = RetryPolicy {
+            retries: 0,
+            interval: Duration::ZERO,
+        };
+        attempts.store(0, Ordering::SeqCst);
+        assert!(deliver(&webhook, &alert(AlertStatus::Firing), retry)
+            .await
+            .is_err());
+    }
+}
@@ -1,151 +1,48 @@
-use crate::config::CoinType;
-use crate::handle::CoinEntity;
+use crate::amount::{parse_atomics, Balance};
+use crate::grpc::grpc_endpoint;
 use anyhow::Result;
 use cosmos_sdk_proto::cosmos::bank::v1beta1::{
-    query_client::QueryClient, QueryAllBalancesRequest, QueryBalanceRequest,
+    query_client::QueryClient, Metadata, QueryAllBalancesRequest, QueryBalanceRequest,
+    QueryDenomMetadataRequest,
 };
 use cosmos_sdk_proto::cosmos::base::query::v1beta1::PageRequest;
-use cosmos_sdk_proto::cosmos::base::v1beta1::Coin;
 use cosmos_sdk_proto::cosmwasm::wasm::v1::{
     query_client::QueryClient as WasmQueryClient, QuerySmartContractStateRequest,
 };
-use cw20::{BalanceResponse, Cw20QueryMsg::Balance};
+use cw20::{BalanceResponse, Cw20QueryMsg};
 use http::uri::Uri;
 use serde_json::{from_slice, to_vec};
 use std::str::FromStr;
-use tendermint_rpc::Url;
+use tonic::transport::Channel;
 use web3::contract::{Contract, Options};
-use web3::types::Address;
-
-impl CoinType {
-    pub async fn get_balance(
-        &self,
-        address: String,
-        denom: String,
-        contract_address: Option<String>,
-        grpc_addr: Option<Url>,
-        evm_addr: Option<Url>,
-    ) -> Result<String> {
-        match self {
-            CoinType::COSMOS => {
-                get_cosmos_balance(address, denom, grpc_addr.unwrap().to_string()).await
-            }
-            CoinType::CW20 => {
-                get_cw20_balance(
-                    address,
-                    contract_address.unwrap().to_string(),
-                    grpc_addr.unwrap().to_string(),
-                )
-                .await
-            }
-            CoinType::EVM => get_evm_balance(address, evm_addr.unwrap().to_string()).await,
-            CoinType::EVM_ERC20 => {
-                get_evm_erc20_balance(
-                    address,
-                    contract_address.unwrap().to_string(),
-                    evm_addr.unwrap().to_string(),
-                )
-                .await
-            }
-        }
-    }
-    pub async fn get_balances(
-        &self,
-        address: String,
-        coin_entities: &[CoinEntity],
-        grpc_addr: Option<Url>,
-        evm_addr: Option<Url>,
-    ) -> Result<(Vec<Coin>, String)> {
-        match self {
-            CoinType::COSMOS => {
-                let grpc_addr = grpc_addr.unwrap().to_string();
-                get_cosmos_balances(address, grpc_addr.clone())
-                    .await
-                    .map(|balances| (balances, grpc_addr.clone()))
-                    .map_err(|e| crate::error::Error::query_error(e.to_string(), grpc_addr).into())
-            }
-            CoinType::CW20 => {
-                let grpc_addr = grpc_addr.unwrap().to_string();
-                let mut coins = Vec::<Coin>::new();
-                for coin_entity in coin_entities {
-                    let contract_address = coin_entity.contract_address.clone().unwrap();
-                    let balance =
-                        get_cw20_balance(address.clone(), contract_address, grpc_addr.clone())
-                            .await
-                            .map_err(|e| {
-                                crate::error::Error::query_error(e.to_string(), grpc_addr.clone())
-                            })?;
-                    coins.push(Coin {
-                        denom: coin_entity.denom.clone(),
-                        amount: balance,
-                    });
-                }
-                Ok((coins, grpc_addr))
-            }
-            CoinType::EVM => {
-                let evm_addr = evm_addr.unwrap().to_string();
-                let denom = coin_entities.first().unwrap().denom.clone();
-                get_evm_balance(address, evm_addr.clone())
-                    .await
-                    .map(|balance| {
-                        (
-                            vec![Coin {
-                                denom,
-                                amount: balance,
-                            }],
-                            evm_addr.clone(),
-                        )
-                    })
-                    .map_err(|e| crate::error::Error::query_error(e.to_string(), evm_addr).into())
-            }
-            CoinType::EVM_ERC20 => {
-                let evm_addr = evm_addr.unwrap().to_string();
-                let mut coins = Vec::<Coin>::new();
-                for coin_entity in coin_entities {
-                    let contract_address = coin_entity.contract_address.clone().unwrap();
-                    let balance = get_evm_erc20_balance(
-                        address.clone(),
-                        contract_address,
-                        evm_addr.clone(),
-                    )
-                    .await
-                    .map_err(|e| {
-                        crate::error::Error::query_error(e.to_string(), evm_addr.clone())
-                    })?;
-                    coins.push(Coin {
-                        denom: coin_entity.denom.clone(),
-                        amount: balance,
-                    });
-                }
-                Ok((coins, evm_addr))
-            }
-        }
-    }
-}
+use web3::types::{Address, U256};
 
 /// Fetches on-chain balance of given address and chain
-pub async fn get_cosmos_balance(
-    address: String,
-    denom: String,
-    grpc_addr: String,
-) -> Result<String> {
+pub async fn get_cosmos_balance(address: String, denom: String, grpc_addr: String) -> Result<U256> {
     let mut query_client = create_grpc_client(grpc_addr.parse::<Uri>()?, QueryClient::new).await?;
     let request = QueryBalanceRequest {
         address,
         denom: denom.clone(),
     };
-    Ok(query_client
+    let amount = query_client
         .balance(request)
         .await?
         .into_inner()
         .balance
         .map(|coin| coin.amount)
-        .ok_or_else(|| crate::error::Error::get_cosmos_balance(denom))?)
+        .ok_or_else(|| crate::error::Error::get_cosmos_balance(denom))?;
+    Ok(parse_atomics(&amount)?)
 }
 
 /// Fetches on-chain balance of given address and chain
-pub async fn get_cosmos_balances(address: String, grpc_addr: String) -> Result<Vec<Coin>> {
-    let mut query_client = create_grpc_client(grpc_addr.parse::<Uri>()?, QueryClient::new).await?;
+pub async fn get_cosmos_balances(address: String, grpc_addr: String) -> Result<Vec<Balance>> {
+    let channel = create_grpc_client(grpc_addr.parse::<Uri>()?, |channel| channel).await?;
+    query_cosmos_balances(channel, address).await
+}
+
+/// Fetches all bank balances of the given address over an existing channel
+pub async fn query_cosmos_balances(channel: Channel, address: String) -> Result<Vec<Balance>> {
+    let mut query_client = QueryClient::new(channel);
 
     let mut page_request = PageRequest {
         key: vec![],
@@ -160,11 +57,16 @@ pub async fn get_cosmos_balances(address: String, grpc_addr: String) -> Result<V
         ..Default::default()
     };
 
-    let mut coins = Vec::<Coin>::new();
+    let mut coins = Vec::<Balance>::new();
 
     let mut response = query_client.all_balances(request).await?.into_inner();
 
-    coins.extend(response.balances);
+    for coin in response.balances {
+        coins.push(Balance {
+            amount: parse_atomics(&coin.amount)?,
+            denom: coin.denom,
+        });
+    }
 
     while let Some(pagination) = response.pagination {
         if pagination.next_key.is_empty() {
@@ -177,18 +79,22 @@ pub async fn get_cosmos_balances(address: String, grpc_addr: String) -> Result<V
             ..Default::default()
         };
         response = query_client.all_balances(request).await?.into_inner();
-        coins.extend(response.balances);
+        for coin in response.balances {
+            coins.push(Balance {
+                amount: parse_atomics(&coin.amount)?,
+                denom: coin.denom,
+            });
+        }
     }
 
     Ok(coins)
 }
 
-pub async fn get_evm_balance(address: String, evm_addr: String) -> Result<String> {
+pub async fn get_evm_balance(address: String, evm_addr: String) -> Result<U256> {
     let transport = web3::transports::Http::new(&evm_addr)?;
     let web3 = web3::Web3::new(transport);
     let account = Address::from_str(&address)?;
-    let balance = web3.eth().balance(account, None).await?;
-    Ok(balance.as_u128().to_string())
+    Ok(web3.eth().balance(account, None).await?)
 }
 
 /// Fetches ERC-20 token balance via `balanceOf(address)` eth_call.
@@ -197,7 +103,7 @@ pub async fn get_evm_erc20_balance(
     address: String,
     contract_address: String,
     evm_addr: String,
-) -> Result<String> {
+) -> Result<U256> {
     // Minimal ERC-20 ABI — only balanceOf is needed
     let abi = r#"[{
         "constant": true,
@@ -215,23 +121,100 @@ pub async fn get_evm_erc20_balance(
 
     let contract = Contract::from_json(web3.eth(), contract_addr, abi.as_bytes())?;
 
-    let balance: web3::types::U256 = contract
+    let balance: U256 = contract
         .query("balanceOf", (wallet_addr,), None, Options::default(), None)
         .await?;
 
-    Ok(balance.as_u128().to_string())
+    Ok(balance)
 }
 
 pub async fn get_cw20_balance(
     address: String,
     contract_address: String,
     grpc_addr: String,
-) -> Result<String> {
-    let mut query_client =
-        create_grpc_client(grpc_addr.parse::<Uri>()?, WasmQueryClient::new).await?;
+) -> Result<U256> {
+    let channel = create_grpc_client(grpc_addr.parse::<Uri>()?, |channel| channel).await?;
+    query_cw20_balance(channel, address, contract_address).await
+}
+
+/// Fetches the bank metadata of the given denom over an existing channel,
+/// `None` when the chain has none, as for most IBC denoms
+pub async fn query_denom_metadata(channel: Channel, denom: String) -> Result<Option<Metadata>> {
+    let mut query_client = QueryClient::new(channel);
+    match query_client
+        .denom_metadata(QueryDenomMetadataRequest { denom })
+        .await
+    {
+        Ok(response) => Ok(response.into_inner().metadata),
+        Err(status) if status.code() == tonic::Code::NotFound => Ok(None),
+        Err(status) => Err(status.into()),
+    }
+}
+
+/// Origin of an IBC denom, as returned by the ibc-transfer module. Not part of
+/// cosmos-sdk-proto, so declared here.
+#[derive(Clone, PartialEq, prost::Message)]
+pub struct DenomTrace {
+    /// Port and channel pairs the token went through, e.g. `transfer/channel-0`
+    #[prost(string, tag = "1")]
+    pub path: String,
+    #[prost(string, tag = "2")]
+    pub base_denom: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct QueryDenomTraceRequest {
+    #[prost(string, tag = "1")]
+    hash: String,
+}
+
+#[derive(Clone, PartialEq, prost::Message)]
+struct QueryDenomTraceResponse {
+    #[prost(message, optional, tag = "1")]
+    denom_trace: Option<DenomTrace>,
+}
+
+/// Fetches the origin of an `ibc/{hash}` denom over an existing channel,
+/// `None` when the chain does not know the hash or has no ibc-transfer module
+pub async fn query_denom_trace(channel: Channel, hash: String) -> Result<Option<DenomTrace>> {
+    let mut grpc = tonic::client::Grpc::new(channel);
+    grpc.ready().await?;
+    let path =
+        http::uri::PathAndQuery::from_static("/ibc.applications.transfer.v1.Query/DenomTrace");
+    match grpc
+        .unary(
+            tonic::Request::new(QueryDenomTraceRequest { hash }),
+            path,
+            tonic::codec::ProstCodec::default(),
+        )
+        .await
+    {
+        Ok(response) => {
+            let response: QueryDenomTraceResponse = response.into_inner();
+            Ok(response.denom_trace)
+        }
+        Err(status)
+            if matches!(
+                status.code(),
+                tonic::Code::NotFound | tonic::Code::Unimplemented
+            ) =>
+        {
+            Ok(None)
+        }
+        Err(status) => Err(status.into()),
+    }
+}
+
+/// Fetches the CW20 balance of the given address over an existing channel
+pub async fn query_cw20_balance(
+    channel: Channel,
+    address: String,
+    contract_address: String,
+) -> Result<U256> {
+    let mut query_client = WasmQueryClient::new(channel);
     let request = QuerySmartContractStateRequest {
         address: contract_address,
-        query_data: to_vec(&Balance { address })?,
+        query_data: to_vec(&Cw20QueryMsg::Balance { address })?,
     };
     let resp: BalanceResponse = from_slice(
         &query_client
@@ -240,18 +223,15 @@ pub async fn get_cw20_balance(
             .into_inner()
             .data,
     )?;
-    Ok(resp.balance.to_string())
+    Ok(U256::from(resp.balance.u128()))
 }
 
 /// Helper function to create a gRPC client.
 pub async fn create_grpc_client<T>(
     grpc_addr: Uri,
-    client_constructor: impl FnOnce(tonic::transport::Channel) -> T,
+    client_constructor: impl FnOnce(Channel) -> T,
 ) -> Result<T, crate::error::Error> {
-    let tls_config = tonic::transport::ClientTlsConfig::new().with_native_roots();
-    let channel = tonic::transport::Channel::builder(grpc_addr)
-        .tls_config(tls_config)
-        .map_err(crate::error::Error::grpc_transport)?
+    let channel = grpc_endpoint(grpc_addr)?
         .connect()
         .await
         .map_err(crate::error::Error::grpc_transport)?;
@@ -260,8 +240,6 @@ pub async fn create_grpc_client<T>(
 
 #[cfg(test)]
 mod tests {
-    use more_asserts::assert_ge;
-
     use super::*;
 
     // TODO: use mock server instead
@@ -274,7 +252,6 @@ mod tests {
             .await
             .unwrap();
         println!("{:?}", balance);
-        assert_ne!(balance, "".to_string());
     }
 
     #[actix_rt::test]
@@ -283,7 +260,7 @@ mod tests {
         let evm_addr = "https://ethereum-rpc.publicnode.com".to_string();
         let balance = get_evm_balance(address, evm_addr).await.unwrap();
         println!("{:?}", balance);
-        assert_ne!(balance, "".to_string());
+        assert!(!balance.is_zero());
     }
 
     #[actix_rt::test]
@@ -292,7 +269,7 @@ mod tests {
         let endpoint_addr = "https://grpc.dukong.mantrachain.io".to_string();
         let balances = get_cosmos_balances(address, endpoint_addr).await.unwrap();
         println!("{:#?}", balances);
-        assert_ge!(balances.len(), 0);
+        assert!(balances.iter().all(|balance| !balance.denom.is_empty()));
     }
 
     #[actix_rt::test]
@@ -305,7 +282,6 @@ mod tests {
             .await
             .unwrap();
         println!("{:?}", balance);
-        assert_ne!(balance, "".to_string());
     }
 
     #[actix_rt::test]
@@ -318,10 +294,9 @@ mod tests {
             .await
             .unwrap();
         println!("MantraUSD raw balance: {}", balance);
-        let balance_u128: u128 = balance.parse().unwrap();
         // Treasury must hold at least 100 MantraUSD (100 * 10^6) to keep bridge running
         assert!(
-            balance_u128 > 0,
+            !balance.is_zero(),
             "treasury MantraUSD balance should be non-zero"
         );
     }
@@ -0,0 +1,89 @@
+//! Config reload triggers
+use log::info;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::signal::unix::{signal, Signal, SignalKind};
+use tokio::time::Interval;
+
+/// Fires on SIGHUP and, when a watch interval is given, when the modification
+/// time of the config file changes.
+pub struct ReloadTrigger {
+    path: PathBuf,
+    sighup: Signal,
+    watch: Option<Interval>,
+    modified: Option<SystemTime>,
+}
+
+impl ReloadTrigger {
+    pub fn new(path: &Path, watch: Option<Duration>) -> io::Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            sighup: signal(SignalKind::hangup())?,
+            watch: watch.map(tokio::time::interval),
+            modified: modified(path),
+        })
+    }
+
+    /// Wait until the config should be reloaded.
+    pub async fn changed(&mut self) {
+        loop {
+            tokio::select! {
+                _ = self.sighup.recv() => {
+                    info!("received SIGHUP");
+                    self.modified = modified(&self.path);
+                    return;
+                }
+                _ = tick(&mut self.watch) => {
+                    let modified = modified(&self.path);
+                    // a file being replaced may briefly be missing
+                    if modified.is_some() && modified != self.modified {
+                        info!("config file changed: {}", self.path.display());
+                        self.modified = modified;
+                        return;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+async fn tick(watch: &mut Option<Interval>) {
+    match watch {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn fires_when_the_file_changes() {
+        let path = std::env::temp_dir().join(format!("reload-{}.toml", std::process::id()));
+        std::fs::write(&path, "").unwrap();
+        let mut trigger = ReloadTrigger::new(&path, Some(Duration::from_millis(10))).unwrap();
+        // make sure the new mtime differs on coarse-grained filesystems
+        let later = SystemTime::now() + Duration::from_secs(5);
+        std::fs::File::options()
+            .write(true)
+            .open(&path)
+            .unwrap()
+            .set_modified(later)
+            .unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), trigger.changed())
+            .await
+            .expect("reload trigger fired");
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,252 @@
+//! Silences suppressing notifications, from the config and the HTTP API
+use crate::config::SilenceConfig;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::SystemTime;
+use warp::http::StatusCode;
+use warp::{Filter, Rejection, Reply};
+
+/// Problems of a silence, the same for the config and the HTTP API.
+pub fn check_silence(silence: &SilenceConfig) -> Vec<String> {
+    let mut messages = Vec::new();
+    if silence.chain_id.is_none()
+        && silence.address.is_none()
+        && silence.role.is_none()
+        && silence.denom.is_none()
+    {
+        messages.push("set at least one of chain_id, address, role or denom".to_string());
+    }
+    if silence
+        .starts_at
+        .is_some_and(|starts_at| starts_at >= silence.ends_at)
+    {
+        messages.push("ends_at must be after starts_at".to_string());
+    }
+    messages
+}
+
+/// A silence as listed by the HTTP API.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Silence {
+    /// `config-N` for the Nth silence of the config, a number for those
+    /// created over HTTP.
+    pub id: String,
+    pub active: bool,
+    #[serde(flatten)]
+    pub silence: SilenceConfig,
+}
+
+/// The silences of the config, replaced on reload, and those created over
+/// HTTP, kept until they end or are deleted. The latter are lost on restart.
+#[derive(Default)]
+pub struct Silences {
+    configured: RwLock<Vec<SilenceConfig>>,
+    created: Mutex<(u64, BTreeMap<u64, SilenceConfig>)>,
+}
+
+impl Silences {
+    pub fn configure(&self, silences: &[SilenceConfig]) {
+        *self.configured.write().unwrap() = silences.to_vec();
+    }
+
+    /// Add a silence, or report why it is invalid.
+    pub fn create(&self, silence: SilenceConfig, now: SystemTime) -> Result<Silence, String> {
+        let messages = check_silence(&silence);
+        if !messages.is_empty() {
+            return Err(messages.join(", "));
+        }
+        if silence.ends_at <= now {
+            return Err("ends_at is in the past".to_string());
+        }
+        let mut created = self.created.lock().unwrap();
+        let (last_id, silences) = &mut *created;
+        silences.retain(|_, silence| now < silence.ends_at);
+        *last_id += 1;
+        silences.insert(*last_id, silence.clone());
+        Ok(Silence {
+            id: last_id.to_string(),
+            active: silence.is_active(now),
+            silence,
+        })
+    }
+
+    /// Delete a silence created over HTTP, returns whether it existed.
+    pub fn remove(&self, id: u64) -> bool {
+        self.created.lock().unwrap().1.remove(&id).is_some()
+    }
+
+    /// Every silence of the config, and those created over HTTP not ended yet.
+    pub fn list(&self, now: SystemTime) -> Vec<Silence> {
+        let configured = self
+            .configured
+            .read()
+            .unwrap()
+            .iter()
+            .enumerate()
+            .map(|(index, silence)| (format!("config-{}", index + 1), silence.clone()))
+            .collect::<Vec<_>>();
+        let mut created = self.created.lock().unwrap();
+        created.1.retain(|_, silence| now < silence.ends_at);
+        let created = created
+            .1
+            .iter()
+            .map(|(id, silence)| (id.to_string(), silence.clone()));
+        configured
+            .into_iter()
+            .chain(created)
+            .map(|(id, silence)| Silence {
+                id,
+                active: silence.is_active(now),
+                silence,
+            })
+            .collect()
+    }
+
+    /// Whether an active silence matches the coin.
+    pub fn is_silenced(
+        &self,
+        chain_id: &str,
+        address: &str,
+        role: &str,
+        denom: &str,
+        now: SystemTime,
+    ) -> bool {
+        let silenced = |silence: &SilenceConfig| {
+            silence.is_active(now) && silence.matches(chain_id, address, role, denom)
+        };
+        self.configured.read().unwrap().iter().any(silenced)
+            || self.created.lock().unwrap().1.values().any(silenced)
+    }
+}
+
+/// `GET` and `POST /api/silences` to list and create silences, and
+/// `DELETE /api/silences/<id>` to end one created over HTTP.
+pub fn routes(
+    silences: Arc<Silences>,
+) -> impl Filter<Extract = (impl Reply,), Error = Rejection> + Clone {
+    let with_silences = warp::any().map(move || silences.clone());
+    let list = warp::path!("api" / "silences")
+        .and(warp::get())
+        .and(with_silences.clone())
+        .map(|silences: Arc<Silences>| warp::reply::json(&silences.list(SystemTime::now())));
+    let create = warp::path!("api" / "silences")
+        .and(warp::post())
+        .and(warp::body::content_length_limit(16 * 1024))
+        .and(warp::body::json())
+        .and(with_silences.clone())
+        .map(|silence: SilenceConfig, silences: Arc<Silences>| {
+            match silences.create(silence, SystemTime::now()) {
+                Ok(created) => {
+                    warp::reply::with_status(warp::reply::json(&created), StatusCode::CREATED)
+                }
+                Err(error) => warp::reply::with_status(
+                    warp::reply::json(&serde_json::json!({ "error": error })),
+                    StatusCode::BAD_REQUEST,
+                ),
+            }
+        });
+    let delete = warp::path!("api" / "silences" / u64)
+        .and(warp::delete())
+        .and(with_silences)
+        .map(|id, silences: Arc<Silences>| match silences.remove(id) {
+            true => StatusCode::NO_CONTENT,
+            false => StatusCode::NOT_FOUND,
+        });
+    list.or(create).or(delete)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn silence(toml: &str) -> SilenceConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn matches_active_silences() {
+        let now = humantime::parse_rfc3339("2024-06-01T12:00:00Z").unwrap();
+        let silences = Silences::default();
+        silences.configure(&[silence(
+            "chain_id = 'mantra-1'\nrole = 'relayer'\ndenom = 'om'\nstarts_at = '2024-06-01T10:00:00Z'\nends_at = '2024-06-01T14:00:00Z'",
+        )]);
+        let address = "mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0";
+        assert!(silences.is_silenced("mantra-1", address, "relayer", "OM", now));
+        assert!(!silences.is_silenced("mantra-1", address, "faucet", "OM", now));
+        assert!(!silences.is_silenced("mantra-1", address, "relayer", "USDC", now));
+        assert!(!silences.is_silenced("mantra-1", address, "relayer", "OM", now + HOUR * 2));
+        assert!(!silences.is_silenced("mantra-1", address, "relayer", "OM", now - HOUR * 3));
+
+        assert!(
+            check_silence(&silence("ends_at = '2024-06-01T14:00:00Z'"))[0].contains("at least one")
+        );
+        assert!(check_silence(&silence(
+            "role = 'relayer'\nstarts_at = '2024-06-01T14:00:00Z'\nends_at = '2024-06-01T14:00:00Z'"
+        ))[0]
+            .contains("after starts_at"));
+    }
+
+    #[tokio::test]
+    async fn api_creates_lists_and_deletes_silences() {
+        let silences = Arc::new(Silences::default());
+        silences.configure(&[silence("role = 'faucet'\nends_at = '2999-01-01T00:00:00Z'")]);
+        let api = routes(silences.clone());
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/api/silences")
+            .json(&serde_json::json!({
+                "address": "mantra1q8mgs55hfgkm7d5rret439997x87s2ekwcxlv0",
+                "ends_at": "2999-01-01T00:00:00Z",
+                "comment": "migrating the relayer",
+            }))
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let created: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        assert_eq!(created["id"], "1");
+        assert_eq!(created["active"], true);
+
+        let response = warp::test::request()
+            .method("POST")
+            .path("/api/silences")
+            .json(&serde_json::json!({ "ends_at": "2999-01-01T00:00:00Z" }))
+            .reply(&api)
+            .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = warp::test::request()
+            .path("/api/silences")
+            .reply(&api)
+            .await;
+        let listed: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+        let ids = listed
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|silence| silence["id"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(ids, ["config-1", "1"]);
+        assert_eq!(listed[1]["comment"], "migrating the relayer");
+
+        let delete = |path: &'static str| {
+            warp::test::request()
+                .method("DELETE")
+                .path(path)
+                .reply(&api)
+        };
+        assert_eq!(
+            delete("/api/silences/1").await.status(),
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(
+            delete("/api/silences/1").await.status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(silences.list(SystemTime::now()).len(), 1);
+    }
+}
@@ -0,0 +1,522 @@
+//! Pluggable balance sources
+//!
+//! Every `coin_type` in the config is served by a [`BalanceSource`] looked up
+//! from a [`SourceRegistry`]. The built-in sources cover the native, CW20,
+//! EVM and ERC-20 backends; downstream crates can register their own sources
+//! under a custom `coin_type` and hand the registry to
+//! [`crate::handle::account_status_collector`].
+use crate::amount::Balance;
+use crate::config::{default, ChainConfig, CoinType};
+use crate::endpoint::EndpointPool;
+use crate::error::{Error, ErrorDetail, QueryErrorClass};
+use crate::grpc::ChannelCache;
+use crate::handle::CoinEntity;
+use crate::query::{
+    get_evm_balance, get_evm_erc20_balance, query_cosmos_balances, query_cw20_balance,
+};
+use async_trait::async_trait;
+use futures::future::join_all;
+use log::warn;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Query endpoints of the chain a balance source is asked about, shared by
+/// every address watched on that chain.
+#[derive(Clone, Debug)]
+pub struct ChainEndpoints {
+    pub grpc: Arc<EndpointPool>,
+    pub evm: Arc<EndpointPool>,
+}
+
+impl ChainEndpoints {
+    pub fn from_config(chain_config: &ChainConfig) -> Self {
+        Self {
+            grpc: Arc::new(EndpointPool::new(
+                "gRPC",
+                &chain_config.grpc_addr,
+                chain_config.endpoint_selection,
+                chain_config.query_timeout,
+            )),
+            evm: Arc::new(EndpointPool::new(
+                "EVM",
+                &chain_config.evm_addr,
+                chain_config.endpoint_selection,
+                chain_config.query_timeout,
+            )),
+        }
+    }
+}
+
+impl Default for ChainEndpoints {
+    fn default() -> Self {
+        Self {
+            grpc: Arc::new(EndpointPool::new(
+                "gRPC",
+                &[],
+                Default::default(),
+                default::query_timeout(),
+            )),
+            evm: Arc::new(EndpointPool::new(
+                "EVM",
+                &[],
+                Default::default(),
+                default::query_timeout(),
+            )),
+        }
+    }
+}
+
+/// Outcome of querying one coin: its balance and the endpoint that served it.
+pub type CoinBalance = Result<(Balance, String), Error>;
+
+/// The error for a coin a [`BalanceSource`] returned no result for.
+pub fn missing_balance(coin_entity: &CoinEntity) -> Error {
+    Error::query_error(
+        "the balance source returned no result for the coin".to_string(),
+        String::new(),
+        coin_entity.coin_type.to_string(),
+        coin_entity.denom.clone(),
+        QueryErrorClass::Other,
+    )
+}
+
+/// The `results` of a source for `coin_entities`, one per coin: the coins it
+/// returned no result for fail with [`missing_balance`], extra results are
+/// dropped.
+pub fn complete_balances(
+    coin_entities: &[CoinEntity],
+    results: Vec<CoinBalance>,
+) -> Vec<CoinBalance> {
+    if results.len() != coin_entities.len() {
+        warn!(
+            "balance source returned {} results for {} coins",
+            results.len(),
+            coin_entities.len()
+        );
+    }
+    let mut results = results.into_iter();
+    coin_entities
+        .iter()
+        .map(|coin_entity| {
+            results
+                .next()
+                .unwrap_or_else(|| Err(missing_balance(coin_entity)))
+        })
+        .collect()
+}
+
+/// A backend able to fetch the balances of one address.
+#[async_trait]
+pub trait BalanceSource: Send + Sync {
+    /// Fetches the balances of `coin_entities` held by `address`, returning
+    /// one result per coin entity, in the same order, so that a failing coin
+    /// does not hide the others. Failures are reported as
+    /// [`crate::error::ErrorDetail::QueryError`].
+    async fn get_balances(
+        &self,
+        address: String,
+        coin_entities: &[CoinEntity],
+        endpoints: &ChainEndpoints,
+    ) -> Vec<CoinBalance>;
+}
+
+/// Balance sources keyed by the `coin_type` they serve.
+#[derive(Clone)]
+pub struct SourceRegistry {
+    sources: HashMap<CoinType, Arc<dyn BalanceSource>>,
+}
+
+impl SourceRegistry {
+    /// Creates a registry without any source.
+    pub fn empty() -> Self {
+        Self {
+            sources: HashMap::new(),
+        }
+    }
+
+    /// Registers `source` for `coin_type`, replacing any previous source.
+    pub fn register(&mut self, coin_type: CoinType, source: impl BalanceSource + 'static) {
+        self.sources.insert(coin_type, Arc::new(source));
+    }
+
+    pub fn get(&self, coin_type: &CoinType) -> Option<Arc<dyn BalanceSource>> {
+        self.sources.get(coin_type).cloned()
+    }
+
+    pub fn contains(&self, coin_type: &CoinType) -> bool {
+        self.sources.contains_key(coin_type)
+    }
+
+    /// The coin types with a source, for [`crate::config::load_with`].
+    pub fn coin_types(&self) -> Vec<CoinType> {
+        let mut coin_types = self.sources.keys().cloned().collect::<Vec<_>>();
+        coin_types.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        coin_types
+    }
+}
+
+impl Default for SourceRegistry {
+    /// Creates a registry with the built-in sources, sharing one gRPC channel cache.
+    fn default() -> Self {
+        let channels = ChannelCache::new();
+        let mut registry = Self::empty();
+        registry.register(CoinType::COSMOS, CosmosSource::new(channels.clone()));
+        registry.register(CoinType::CW20, Cw20Source::new(channels));
+        registry.register(CoinType::EVM, EvmSource);
+        registry.register(CoinType::EVM_ERC20, EvmErc20Source);
+        registry
+    }
+}
+
+/// Native bank balances through gRPC `AllBalances`.
+pub struct CosmosSource {
+    channels: ChannelCache,
+}
+
+impl CosmosSource {
+    pub fn new(channels: ChannelCache) -> Self {
+        Self { channels }
+    }
+}
+
+#[async_trait]
+impl BalanceSource for CosmosSource {
+    async fn get_balances(
+        &self,
+        address: String,
+        coin_entities: &[CoinEntity],
+        endpoints: &ChainEndpoints,
+    ) -> Vec<CoinBalance> {
+        let denoms = coin_entities
+            .iter()
+            .map(|coin_entity| coin_entity.denom.as_str())
+            .collect::<Vec<_>>()
+            .join(",");
+        let result = endpoints
+            .grpc
+            .execute(&CoinType::COSMOS, &denoms, |grpc_addr| {
+                let address = address.clone();
+                async move {
+                    let channel = self.channels.channel(&grpc_addr)?;
+                    query_cosmos_balances(channel, address)
+                        .await
+                        .inspect_err(|e| self.channels.evict_on_error(&grpc_addr, e))
+                }
+            })
+            .await;
+        // one query serves every denom, denoms missing from the response hold nothing
+        coin_entities
+            .iter()
+            .map(|coin_entity| match &result {
+                Ok((balances, grpc_addr)) => {
+                    let amount = balances
+                        .iter()
+                        .find(|balance| balance.denom == coin_entity.denom)
+                        .map(|balance| balance.amount)
+                        .unwrap_or_default();
+                    Ok((
+                        Balance {
+                            denom: coin_entity.denom.clone(),
+                            amount,
+                        },
+                        grpc_addr.clone(),
+                    ))
+                }
+                Err(e) => Err(for_denom(e, &coin_entity.denom)),
+            })
+            .collect()
+    }
+}
+
+/// CW20 balances through the wasm smart query `balance`, one contract at a time.
+pub struct Cw20Source {
+    channels: ChannelCache,
+}
+
+impl Cw20Source {
+    pub fn new(channels: ChannelCache) -> Self {
+        Self { channels }
+    }
+}
+
+#[async_trait]
+impl BalanceSource for Cw20Source {
+    async fn get_balances(
+        &self,
+        address: String,
+        coin_entities: &[CoinEntity],
+        endpoints: &ChainEndpoints,
+    ) -> Vec<CoinBalance> {
+        join_all(coin_entities.iter().map(|coin_entity| {
+            let address = address.clone();
+            async move {
+                let Some(contract_address) = coin_entity.contract_address.clone() else {
+                    return Err(missing_contract_address(
+                        &CoinType::CW20,
+                        &coin_entity.denom,
+                    ));
+                };
+                let (amount, grpc_addr) = endpoints
+                    .grpc
+                    .execute(&CoinType::CW20, &coin_entity.denom, |grpc_addr| {
+                        let address = address.clone();
+                        let contract_address = contract_address.clone();
+                        async move {
+                            let channel = self.channels.channel(&grpc_addr)?;
+                            query_cw20_balance(channel, address, contract_address)
+                                .await
+                                .inspect_err(|e| self.channels.evict_on_error(&grpc_addr, e))
+                        }
+                    })
+                    .await?;
+                Ok((
+                    Balance {
+                        denom: coin_entity.denom.clone(),
+                        amount,
+                    },
+                    grpc_addr,
+                ))
+            }
+        }))
+        .await
+    }
+}
+
+/// Native EVM balances through `eth_getBalance`.
+pub struct EvmSource;
+
+#[async_trait]
+impl BalanceSource for EvmSource {
+    async fn get_balances(
+        &self,
+        address: String,
+        coin_entities: &[CoinEntity],
+        endpoints: &ChainEndpoints,
+    ) -> Vec<CoinBalance> {
+        let Some(first) = coin_entities.first() else {
+            return Vec::new();
+        };
+        let result = endpoints
+            .evm
+            .execute(&CoinType::EVM, &first.denom, |evm_addr| {
+                get_evm_balance(address.clone(), evm_addr)
+            })
+            .await;
+        // the native balance is the same for every entry of the native denom
+        coin_entities
+            .iter()
+            .map(|coin_entity| match &result {
+                Ok((amount, evm_addr)) => Ok((
+                    Balance {
+                        denom: coin_entity.denom.clone(),
+                        amount: *amount,
+                    },
+                    evm_addr.clone(),
+                )),
+                Err(e) => Err(for_denom(e, &coin_entity.denom)),
+            })
+            .collect()
+    }
+}
+
+/// ERC-20 balances through `eth_call` → `balanceOf(address)`, one contract at a time.
+pub struct EvmErc20Source;
+
+#[async_trait]
+impl BalanceSource for EvmErc20Source {
+    async fn get_balances(
+        &self,
+        address: String,
+        coin_entities: &[CoinEntity],
+        endpoints: &ChainEndpoints,
+    ) -> Vec<CoinBalance> {
+        join_all(coin_entities.iter().map(|coin_entity| {
+            let address = address.clone();
+            async move {
+                let Some(contract_address) = coin_entity.contract_address.clone() else {
+                    return Err(missing_contract_address(
+                        &CoinType::EVM_ERC20,
+                        &coin_entity.denom,
+                    ));
+                };
+                let (amount, evm_addr) = endpoints
+                    .evm
+                    .execute(&CoinType::EVM_ERC20, &coin_entity.denom, |evm_addr| {
+                        get_evm_erc20_balance(address.clone(), contract_address.clone(), evm_addr)
+                    })
+                    .await?;
+                Ok((
+                    Balance {
+                        denom: coin_entity.denom.clone(),
+                        amount,
+                    },
+                    evm_addr,
+                ))
+            }
+        }))
+        .await
+    }
+}
+
+/// Error for a contract-based coin without `contract_address`, which
+/// `config::load` rejects but a downstream caller may still build.
+fn missing_contract_address(coin_type: &CoinType, denom: &str) -> Error {
+    Error::query_error(
+        "missing contract address".to_string(),
+        String::new(),
+        coin_type.to_string(),
+        denom.to_string(),
+        QueryErrorClass::InvalidRequest,
+    )
+}
+
+/// Copy of a query error shared by several coins, narrowed to one denom.
+fn for_denom(err: &Error, denom: &str) -> Error {
+    match err.detail() {
+        ErrorDetail::QueryError(e) => Error::query_error(
+            e.source.clone(),
+            e.endpoint.clone(),
+            e.coin_type.clone(),
+            denom.to_string(),
+            e.class,
+        ),
+        detail => Error::query_error(
+            detail.to_string(),
+            String::new(),
+            String::new(),
+            denom.to_string(),
+            QueryErrorClass::Other,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use web3::types::U256;
+
+    struct FixedSource;
+
+    #[async_trait]
+    impl BalanceSource for FixedSource {
+        async fn get_balances(
+            &self,
+            _address: String,
+            coin_entities: &[CoinEntity],
+            _endpoints: &ChainEndpoints,
+        ) -> Vec<CoinBalance> {
+            coin_entities
+                .iter()
+                .map(|coin_entity| {
+                    Ok((
+                        Balance {
+                            denom: coin_entity.denom.clone(),
+                            amount: U256::from(42),
+                        },
+                        "fixed".to_string(),
+                    ))
+                })
+                .collect()
+        }
+    }
+
+    fn coin_entity(coin_type: CoinType, denom: &str) -> CoinEntity {
+        CoinEntity {
+            coin_type,
+            contract_address: Some(format!("0x{:0>40}", denom.len())),
+            decimal_place: 6,
+            display_precision: None,
+            denom: denom.to_string(),
+            display_denom: denom.to_uppercase(),
+            display_min_balance: "0".to_string(),
+            min_balance: U256::zero(),
+            display_critical_balance: String::new(),
+            critical_balance: None,
+            display_max_balance: String::new(),
+            max_balance: None,
+            clear_balance: U256::zero(),
+            for_duration: Duration::ZERO,
+        }
+    }
+
+    #[test]
+    fn default_registry_has_builtin_sources() {
+        let registry = SourceRegistry::default();
+        for coin_type in [
+            CoinType::COSMOS,
+            CoinType::CW20,
+            CoinType::EVM,
+            CoinType::EVM_ERC20,
+        ] {
+            assert!(registry.contains(&coin_type));
+        }
+        assert!(!registry.contains(&CoinType::Custom("IN_HOUSE".to_string())));
+    }
+
+    #[actix_rt::test]
+    async fn custom_source_can_be_registered() {
+        let coin_type = CoinType::Custom("IN_HOUSE".to_string());
+        let mut registry = SourceRegistry::default();
+        registry.register(coin_type.clone(), FixedSource);
+
+        let coin_entities = vec![coin_entity(coin_type.clone(), "ufoo")];
+        let results = registry
+            .get(&coin_type)
+            .unwrap()
+            .get_balances(
+                "addr".to_string(),
+                &coin_entities,
+                &ChainEndpoints::default(),
+            )
+            .await;
+        let (balance, endpoint) = results[0].as_ref().unwrap();
+        assert_eq!(endpoint, "fixed");
+        assert_eq!(balance.denom, "ufoo");
+        assert_eq!(balance.amount, U256::from(42));
+    }
+
+    #[test]
+    fn missing_results_fail_their_coins() {
+        let coin_entities = vec![
+            coin_entity(CoinType::COSMOS, "uom"),
+            coin_entity(CoinType::COSMOS, "uusdc"),
+        ];
+        let balance = Balance {
+            denom: "uom".to_string(),
+            amount: U256::from(42),
+        };
+        let results = complete_balances(&coin_entities, vec![Ok((balance, "fixed".to_string()))]);
+        assert_eq!(results.len(), 2);
+        assert!(results[0].is_ok());
+        match results[1].as_ref().unwrap_err().detail() {
+            ErrorDetail::QueryError(e) => assert_eq!(e.denom, "uusdc"),
+            detail => panic!("unexpected error {:?}", detail),
+        }
+    }
+
+    #[actix_rt::test]
+    async fn reports_one_result_per_coin() {
+        let coin_entities = vec![
+            coin_entity(CoinType::EVM_ERC20, "usdc"),
+            coin_entity(CoinType::EVM_ERC20, "usdt"),
+        ];
+        let results = EvmErc20Source
+            .get_balances(
+                "0x0000000000000000000000000000000000000001".to_string(),
+                &coin_entities,
+                &ChainEndpoints::default(),
+            )
+            .await;
+        assert_eq!(results.len(), 2);
+        for (coin_entity, result) in coin_entities.iter().zip(results) {
+            match result.unwrap_err().detail() {
+                ErrorDetail::QueryError(e) => {
+                    assert_eq!(e.denom, coin_entity.denom);
+                    assert_eq!(e.class, QueryErrorClass::NoEndpoint);
+                }
+                detail => panic!("unexpected error {:?}", detail),
+            }
+        }
+    }
+}
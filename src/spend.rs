@@ -0,0 +1,121 @@
+//! Outflow rate and time to depletion from recent balances
+use crate::amount::Amount;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+use web3::types::U256;
+
+/// Balances of one coin of an address over the last `window`, in atomic units.
+#[derive(Clone, Debug)]
+pub struct SpendWindow {
+    window: Duration,
+    samples: VecDeque<(Instant, U256)>,
+}
+
+impl SpendWindow {
+    pub fn new(window: Duration) -> Self {
+        Self {
+            window,
+            samples: VecDeque::new(),
+        }
+    }
+
+    /// Add the balance queried `at`, dropping samples that left the window.
+    /// The latest sample is always kept.
+    pub fn record(&mut self, at: Instant, amount: U256) {
+        self.samples.push_back((at, amount));
+        while let Some((oldest, _)) = self.samples.front() {
+            if self.samples.len() > 1 && at.duration_since(*oldest) > self.window {
+                self.samples.pop_front();
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Whole units spent per hour over the window. Top-ups are ignored, only
+    /// the decreases between consecutive samples count. `None` until two
+    /// samples apart in time were recorded.
+    pub fn outflow_per_hour(&self, decimal_place: u32) -> Option<f64> {
+        let (first, _) = self.samples.front()?;
+        let (last, _) = self.samples.back()?;
+        let elapsed = last.duration_since(*first);
+        if elapsed.is_zero() {
+            return None;
+        }
+        let spent = self
+            .samples
+            .iter()
+            .zip(self.samples.iter().skip(1))
+            .fold(U256::zero(), |spent, ((_, before), (_, after))| {
+                spent + before.saturating_sub(*after)
+            });
+        let spent = Amount::new(spent, decimal_place).to_f64();
+        Some(spent * 3600.0 / elapsed.as_secs_f64())
+    }
+
+    /// How long until the latest balance drops to `threshold` at the current
+    /// outflow rate: zero once at or below it, `None` while nothing is spent.
+    pub fn time_to(&self, threshold: U256, decimal_place: u32) -> Option<Duration> {
+        let (_, latest) = self.samples.back()?;
+        if *latest <= threshold {
+            return Some(Duration::ZERO);
+        }
+        let per_hour = self.outflow_per_hour(decimal_place)?;
+        if per_hour <= 0.0 {
+            return None;
+        }
+        let left = Amount::new(*latest - threshold, decimal_place).to_f64();
+        Duration::try_from_secs_f64(left / per_hour * 3600.0).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOUR: Duration = Duration::from_secs(3600);
+
+    fn sampled(balances: &[u64]) -> (SpendWindow, Instant) {
+        let start = Instant::now();
+        let mut window = SpendWindow::new(HOUR * 6);
+        for (hours, balance) in balances.iter().enumerate() {
+            window.record(start + HOUR * hours as u32, U256::from(*balance));
+        }
+        (window, start)
+    }
+
+    #[test]
+    fn outflow_ignores_top_ups() {
+        let (window, _) = sampled(&[10_000_000]);
+        assert_eq!(window.outflow_per_hour(6), None);
+
+        // spends 2 per hour, topped up by 50 in the third hour
+        let (window, _) = sampled(&[100_000_000, 98_000_000, 146_000_000, 144_000_000]);
+        assert_eq!(window.outflow_per_hour(6), Some(4.0 / 3.0));
+    }
+
+    #[test]
+    fn time_to_threshold_follows_outflow() {
+        let (window, _) = sampled(&[30_000_000, 28_000_000, 26_000_000]);
+        assert_eq!(window.outflow_per_hour(6), Some(2.0));
+        assert_eq!(window.time_to(U256::from(6_000_000), 6), Some(HOUR * 10));
+        assert_eq!(
+            window.time_to(U256::from(26_000_000), 6),
+            Some(Duration::ZERO)
+        );
+
+        let (window, _) = sampled(&[30_000_000, 40_000_000]);
+        assert_eq!(window.time_to(U256::from(6_000_000), 6), None);
+    }
+
+    #[test]
+    fn old_samples_leave_the_window() {
+        let (mut window, start) = sampled(&[100, 90]);
+        window.record(start + HOUR * 7, U256::from(90));
+        // the drop from 100 to 90 happened more than 6 hours ago
+        assert_eq!(window.outflow_per_hour(0), Some(0.0));
+        window.record(start + HOUR * 20, U256::from(80));
+        assert_eq!(window.samples.len(), 1);
+        assert_eq!(window.outflow_per_hour(0), None);
+    }
+}
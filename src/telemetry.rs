@@ -1,16 +1,49 @@
 use lazy_static::lazy_static;
 use log::error;
-use prometheus::{IntGaugeVec, Opts, Registry};
+use prometheus::{GaugeVec, IntCounterVec, IntGaugeVec, Opts, Registry};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
 use warp::{Rejection, Reply};
 
 lazy_static! {
-    pub static ref ACCOUNT_BALANCE_COLLECTOR: IntGaugeVec = IntGaugeVec::new(
+    pub static ref ACCOUNT_BALANCE_COLLECTOR: GaugeVec = GaugeVec::new(
         Opts::new("account_balance", "account balance"),
         &["chain_id", "address", "denom", "role", "balance_url"]
     )
     .expect("metric can be created");
     pub static ref ACCOUNT_STATUS_COLLECTOR: IntGaugeVec = IntGaugeVec::new(
-        Opts::new("account_status", "Account Status. 0: > min_balance, 1: <= min_balance"),
+        Opts::new("account_status", "Account Status. 0: > min_balance, 1: <= min_balance, 2: <= critical_balance"),
+        &["chain_id", "address", "denom", "min_balance", "role", "balance_url"]
+    )
+    .expect("metric can be created");
+    pub static ref ACCOUNT_CRITICAL_STATUS_COLLECTOR: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("account_critical_status", "Account Critical Status, set for coins with a critical_balance. 0: > critical_balance, 1: <= critical_balance"),
+        &["chain_id", "address", "denom", "critical_balance", "role", "balance_url"]
+    )
+    .expect("metric can be created");
+    pub static ref ACCOUNT_CEILING_STATUS_COLLECTOR: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("account_ceiling_status", "Account Ceiling Status, set for coins with a max_balance. 0: <= max_balance, 1: > max_balance"),
+        &["chain_id", "address", "denom", "max_balance", "role", "balance_url"]
+    )
+    .expect("metric can be created");
+    pub static ref ACCOUNT_ALERT_STATE_COLLECTOR: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("account_alert_state", "Account Alert State with clear_balance hysteresis and for delay. 0: ok, 1: pending, 2: firing, 3: resolved"),
+        &["chain_id", "address", "denom", "min_balance", "role", "balance_url"]
+    )
+    .expect("metric can be created");
+    pub static ref ACCOUNT_SILENCED_COLLECTOR: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("account_silenced", "Account Silenced, notifications suppressed by a silence. 0: not silenced, 1: silenced"),
+        &["chain_id", "address", "denom", "role", "balance_url"]
+    )
+    .expect("metric can be created");
+    pub static ref ACCOUNT_SPEND_RATE_COLLECTOR: GaugeVec = GaugeVec::new(
+        Opts::new("account_spend_rate", "Account outflow per hour over its spend_window, top-ups ignored"),
+        &["chain_id", "address", "denom", "role", "balance_url"]
+    )
+    .expect("metric can be created");
+    pub static ref ACCOUNT_TIME_TO_MIN_BALANCE_COLLECTOR: GaugeVec = GaugeVec::new(
+        Opts::new("account_time_to_min_balance_seconds", "Estimated seconds until the balance reaches min_balance at the current spend rate, +Inf while nothing is spent"),
         &["chain_id", "address", "denom", "min_balance", "role", "balance_url"]
     )
     .expect("metric can be created");
@@ -19,8 +52,159 @@ lazy_static! {
         &["chain_id", "address", "role", "balance_url", "query_endpoint_url"]
     )
     .expect("metric can be created");
+    pub static ref ACCOUNT_DENOM_QUERY_STATUS_COLLECTOR: IntGaugeVec = IntGaugeVec::new(
+        Opts::new("account_denom_query_status", "Account Denom Query Status show the balance query of one denom is successful or not. 0: can access, 1: cannot access"),
+        &["chain_id", "address", "denom", "role", "balance_url", "query_endpoint_url"]
+    )
+    .expect("metric can be created");
+    pub static ref ACCOUNT_QUERY_ERRORS_COLLECTOR: IntCounterVec = IntCounterVec::new(
+        Opts::new("account_query_errors_total", "Number of failed account balance queries by error class"),
+        &["chain_id", "address", "role", "coin_type", "error_class", "query_endpoint_url"]
+    )
+    .expect("metric can be created");
+    pub static ref GRPC_CHANNEL_CONNECTS_COLLECTOR: IntCounterVec = IntCounterVec::new(
+        Opts::new("grpc_channel_connects_total", "Number of gRPC channels created per endpoint"),
+        &["endpoint"]
+    )
+    .expect("metric can be created");
+    pub static ref GRPC_CHANNEL_EVICTIONS_COLLECTOR: IntCounterVec = IntCounterVec::new(
+        Opts::new("grpc_channel_evictions_total", "Number of gRPC channels dropped after a connection error per endpoint"),
+        &["endpoint"]
+    )
+    .expect("metric can be created");
 
     pub static ref REGISTRY: Registry = Registry::new();
+
+    static ref ACCOUNT_SERIES: Mutex<HashMap<AccountKey, HashMap<Series, Instant>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// The `chain_id`, `address` and `role` labels shared by every series of one
+/// watched address.
+type AccountKey = (String, String, String);
+
+/// The account collectors whose series are tracked per watched address.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum AccountCollector {
+    Balance,
+    Status,
+    CriticalStatus,
+    CeilingStatus,
+    AlertState,
+    Silenced,
+    SpendRate,
+    TimeToMinBalance,
+    QueryStatus,
+    DenomQueryStatus,
+    QueryErrors,
+}
+
+type Series = (AccountCollector, Vec<String>);
+
+/// Remember the label values of a series set for the address of `chain_id`,
+/// `address` and `role`, and when it was last updated.
+fn track_series(
+    chain_id: &str,
+    address: &str,
+    role: &str,
+    collector: AccountCollector,
+    label_values: &[&str],
+) {
+    let key = (chain_id.to_string(), address.to_string(), role.to_string());
+    let label_values = label_values.iter().map(|value| value.to_string()).collect();
+    ACCOUNT_SERIES
+        .lock()
+        .unwrap()
+        .entry(key)
+        .or_default()
+        .insert((collector, label_values), Instant::now());
+}
+
+fn remove_series((collector, label_values): &Series) {
+    let label_values = label_values.iter().map(String::as_str).collect::<Vec<_>>();
+    // the series may already be gone after a reset
+    let _ = match collector {
+        AccountCollector::Balance => ACCOUNT_BALANCE_COLLECTOR.remove_label_values(&label_values),
+        AccountCollector::Status => ACCOUNT_STATUS_COLLECTOR.remove_label_values(&label_values),
+        AccountCollector::CriticalStatus => {
+            ACCOUNT_CRITICAL_STATUS_COLLECTOR.remove_label_values(&label_values)
+        }
+        AccountCollector::CeilingStatus => {
+            ACCOUNT_CEILING_STATUS_COLLECTOR.remove_label_values(&label_values)
+        }
+        AccountCollector::AlertState => {
+            ACCOUNT_ALERT_STATE_COLLECTOR.remove_label_values(&label_values)
+        }
+        AccountCollector::Silenced => ACCOUNT_SILENCED_COLLECTOR.remove_label_values(&label_values),
+        AccountCollector::SpendRate => {
+            ACCOUNT_SPEND_RATE_COLLECTOR.remove_label_values(&label_values)
+        }
+        AccountCollector::TimeToMinBalance => {
+            ACCOUNT_TIME_TO_MIN_BALANCE_COLLECTOR.remove_label_values(&label_values)
+        }
+        AccountCollector::QueryStatus => {
+            ACCOUNT_QUERY_STATUS_COLLECTOR.remove_label_values(&label_values)
+        }
+        AccountCollector::DenomQueryStatus => {
+            ACCOUNT_DENOM_QUERY_STATUS_COLLECTOR.remove_label_values(&label_values)
+        }
+        AccountCollector::QueryErrors => {
+            ACCOUNT_QUERY_ERRORS_COLLECTOR.remove_label_values(&label_values)
+        }
+    };
+}
+
+/// Remove every series set for the address of `chain_id`, `address` and `role`,
+/// e.g. once it is no longer watched.
+pub fn remove_account_series(chain_id: &str, address: &str, role: &str) {
+    let key = (chain_id.to_string(), address.to_string(), role.to_string());
+    let Some(series) = ACCOUNT_SERIES.lock().unwrap().remove(&key) else {
+        return;
+    };
+    series.keys().for_each(remove_series);
+}
+
+/// Remove the gauge series of the address of `chain_id`, `address` and `role`
+/// that were not updated for `ttl`, e.g. the query status of an endpoint it
+/// no longer uses. Error counters are kept. Returns how many were removed.
+pub fn expire_account_series(chain_id: &str, address: &str, role: &str, ttl: Duration) -> usize {
+    let key = (chain_id.to_string(), address.to_string(), role.to_string());
+    let mut account_series = ACCOUNT_SERIES.lock().unwrap();
+    let Some(series) = account_series.get_mut(&key) else {
+        return 0;
+    };
+    let expired = series
+        .iter()
+        .filter(|((collector, _), updated)| {
+            *collector != AccountCollector::QueryErrors && updated.elapsed() >= ttl
+        })
+        .map(|(series, _)| series.clone())
+        .collect::<Vec<_>>();
+    for stale in expired.iter() {
+        series.remove(stale);
+        remove_series(stale);
+    }
+    expired.len()
+}
+
+/// Drop every account gauge series, as configured by `prometheus.reset`.
+pub fn reset_account_series() {
+    let mut series = ACCOUNT_SERIES.lock().unwrap();
+    ACCOUNT_BALANCE_COLLECTOR.reset();
+    ACCOUNT_STATUS_COLLECTOR.reset();
+    ACCOUNT_CRITICAL_STATUS_COLLECTOR.reset();
+    ACCOUNT_CEILING_STATUS_COLLECTOR.reset();
+    ACCOUNT_ALERT_STATE_COLLECTOR.reset();
+    ACCOUNT_SILENCED_COLLECTOR.reset();
+    ACCOUNT_SPEND_RATE_COLLECTOR.reset();
+    ACCOUNT_TIME_TO_MIN_BALANCE_COLLECTOR.reset();
+    ACCOUNT_QUERY_STATUS_COLLECTOR.reset();
+    ACCOUNT_DENOM_QUERY_STATUS_COLLECTOR.reset();
+    // the error counters are kept, so are their series
+    series.retain(|_, series| {
+        series.retain(|(collector, _), _| *collector == AccountCollector::QueryErrors);
+        !series.is_empty()
+    });
 }
 
 /// A setter for ACCOUNT_BALANCE_COLLECTOR, make sure all the labels are set and types are correct
@@ -30,10 +214,18 @@ pub fn account_balance_setter(
     denom: &str,
     role: &str,
     balance_url: &str,
-    balance: i64,
+    balance: f64,
 ) {
+    let label_values = [chain_id, address, denom, role, balance_url];
+    track_series(
+        chain_id,
+        address,
+        role,
+        AccountCollector::Balance,
+        &label_values,
+    );
     ACCOUNT_BALANCE_COLLECTOR
-        .with_label_values(&[chain_id, address, denom, role, balance_url])
+        .with_label_values(&label_values)
         .set(balance);
 }
 
@@ -47,11 +239,162 @@ pub fn account_status_setter(
     balance_url: &str,
     status: i64,
 ) {
+    let label_values = [chain_id, address, denom, min_balance, role, balance_url];
+    track_series(
+        chain_id,
+        address,
+        role,
+        AccountCollector::Status,
+        &label_values,
+    );
     ACCOUNT_STATUS_COLLECTOR
-        .with_label_values(&[chain_id, address, denom, min_balance, role, balance_url])
+        .with_label_values(&label_values)
+        .set(status);
+}
+
+/// A setter for ACCOUNT_CRITICAL_STATUS_COLLECTOR, make sure all the labels are set and types are correct
+pub fn account_critical_status_setter(
+    chain_id: &str,
+    address: &str,
+    denom: &str,
+    critical_balance: &str,
+    role: &str,
+    balance_url: &str,
+    status: i64,
+) {
+    let label_values = [
+        chain_id,
+        address,
+        denom,
+        critical_balance,
+        role,
+        balance_url,
+    ];
+    track_series(
+        chain_id,
+        address,
+        role,
+        AccountCollector::CriticalStatus,
+        &label_values,
+    );
+    ACCOUNT_CRITICAL_STATUS_COLLECTOR
+        .with_label_values(&label_values)
+        .set(status);
+}
+
+/// A setter for ACCOUNT_CEILING_STATUS_COLLECTOR, make sure all the labels are set and types are correct
+pub fn account_ceiling_status_setter(
+    chain_id: &str,
+    address: &str,
+    denom: &str,
+    max_balance: &str,
+    role: &str,
+    balance_url: &str,
+    status: i64,
+) {
+    let label_values = [chain_id, address, denom, max_balance, role, balance_url];
+    track_series(
+        chain_id,
+        address,
+        role,
+        AccountCollector::CeilingStatus,
+        &label_values,
+    );
+    ACCOUNT_CEILING_STATUS_COLLECTOR
+        .with_label_values(&label_values)
         .set(status);
 }
 
+/// A setter for ACCOUNT_ALERT_STATE_COLLECTOR, make sure all the labels are set and types are correct
+pub fn account_alert_state_setter(
+    chain_id: &str,
+    address: &str,
+    denom: &str,
+    min_balance: &str,
+    role: &str,
+    balance_url: &str,
+    state: i64,
+) {
+    let label_values = [chain_id, address, denom, min_balance, role, balance_url];
+    track_series(
+        chain_id,
+        address,
+        role,
+        AccountCollector::AlertState,
+        &label_values,
+    );
+    ACCOUNT_ALERT_STATE_COLLECTOR
+        .with_label_values(&label_values)
+        .set(state);
+}
+
+/// A setter for ACCOUNT_SILENCED_COLLECTOR, make sure all the labels are set and types are correct
+pub fn account_silenced_setter(
+    chain_id: &str,
+    address: &str,
+    denom: &str,
+    role: &str,
+    balance_url: &str,
+    silenced: i64,
+) {
+    let label_values = [chain_id, address, denom, role, balance_url];
+    track_series(
+        chain_id,
+        address,
+        role,
+        AccountCollector::Silenced,
+        &label_values,
+    );
+    ACCOUNT_SILENCED_COLLECTOR
+        .with_label_values(&label_values)
+        .set(silenced);
+}
+
+/// A setter for ACCOUNT_SPEND_RATE_COLLECTOR, make sure all the labels are set and types are correct
+pub fn account_spend_rate_setter(
+    chain_id: &str,
+    address: &str,
+    denom: &str,
+    role: &str,
+    balance_url: &str,
+    per_hour: f64,
+) {
+    let label_values = [chain_id, address, denom, role, balance_url];
+    track_series(
+        chain_id,
+        address,
+        role,
+        AccountCollector::SpendRate,
+        &label_values,
+    );
+    ACCOUNT_SPEND_RATE_COLLECTOR
+        .with_label_values(&label_values)
+        .set(per_hour);
+}
+
+/// A setter for ACCOUNT_TIME_TO_MIN_BALANCE_COLLECTOR, make sure all the labels are set and types are correct
+pub fn account_time_to_min_balance_setter(
+    chain_id: &str,
+    address: &str,
+    denom: &str,
+    min_balance: &str,
+    role: &str,
+    balance_url: &str,
+    seconds: f64,
+) {
+    let label_values = [chain_id, address, denom, min_balance, role, balance_url];
+    track_series(
+        chain_id,
+        address,
+        role,
+        AccountCollector::TimeToMinBalance,
+        &label_values,
+    );
+    ACCOUNT_TIME_TO_MIN_BALANCE_COLLECTOR
+        .with_label_values(&label_values)
+        .set(seconds);
+}
+
 /// A setter for ACCOUNT_QUERY_STATUS_COLLECTOR, make sure all the labels are set and types are correct
 pub fn account_query_status_setter(
     chain_id: &str,
@@ -61,11 +404,78 @@ pub fn account_query_status_setter(
     query_endpoint_url: &str,
     status: i64,
 ) {
+    let label_values = [chain_id, address, role, balance_url, query_endpoint_url];
+    track_series(
+        chain_id,
+        address,
+        role,
+        AccountCollector::QueryStatus,
+        &label_values,
+    );
     ACCOUNT_QUERY_STATUS_COLLECTOR
-        .with_label_values(&[chain_id, address, role, balance_url, query_endpoint_url])
+        .with_label_values(&label_values)
         .set(status);
 }
 
+/// A setter for ACCOUNT_DENOM_QUERY_STATUS_COLLECTOR, make sure all the labels are set and types are correct
+pub fn account_denom_query_status_setter(
+    chain_id: &str,
+    address: &str,
+    denom: &str,
+    role: &str,
+    balance_url: &str,
+    query_endpoint_url: &str,
+    status: i64,
+) {
+    let label_values = [
+        chain_id,
+        address,
+        denom,
+        role,
+        balance_url,
+        query_endpoint_url,
+    ];
+    track_series(
+        chain_id,
+        address,
+        role,
+        AccountCollector::DenomQueryStatus,
+        &label_values,
+    );
+    ACCOUNT_DENOM_QUERY_STATUS_COLLECTOR
+        .with_label_values(&label_values)
+        .set(status);
+}
+
+/// An incrementer for ACCOUNT_QUERY_ERRORS_COLLECTOR, make sure all the labels are set and types are correct
+pub fn account_query_errors_inc(
+    chain_id: &str,
+    address: &str,
+    role: &str,
+    coin_type: &str,
+    error_class: &str,
+    query_endpoint_url: &str,
+) {
+    let label_values = [
+        chain_id,
+        address,
+        role,
+        coin_type,
+        error_class,
+        query_endpoint_url,
+    ];
+    track_series(
+        chain_id,
+        address,
+        role,
+        AccountCollector::QueryErrors,
+        &label_values,
+    );
+    ACCOUNT_QUERY_ERRORS_COLLECTOR
+        .with_label_values(&label_values)
+        .inc();
+}
+
 pub fn register_custom_metrics() {
     REGISTRY
         .register(Box::new(ACCOUNT_BALANCE_COLLECTOR.clone()))
@@ -73,9 +483,39 @@ pub fn register_custom_metrics() {
     REGISTRY
         .register(Box::new(ACCOUNT_STATUS_COLLECTOR.clone()))
         .expect("collector can be registered");
+    REGISTRY
+        .register(Box::new(ACCOUNT_CRITICAL_STATUS_COLLECTOR.clone()))
+        .expect("collector can be registered");
+    REGISTRY
+        .register(Box::new(ACCOUNT_CEILING_STATUS_COLLECTOR.clone()))
+        .expect("collector can be registered");
+    REGISTRY
+        .register(Box::new(ACCOUNT_ALERT_STATE_COLLECTOR.clone()))
+        .expect("collector can be registered");
+    REGISTRY
+        .register(Box::new(ACCOUNT_SILENCED_COLLECTOR.clone()))
+        .expect("collector can be registered");
+    REGISTRY
+        .register(Box::new(ACCOUNT_SPEND_RATE_COLLECTOR.clone()))
+        .expect("collector can be registered");
+    REGISTRY
+        .register(Box::new(ACCOUNT_TIME_TO_MIN_BALANCE_COLLECTOR.clone()))
+        .expect("collector can be registered");
     REGISTRY
         .register(Box::new(ACCOUNT_QUERY_STATUS_COLLECTOR.clone()))
         .expect("collector can be registered");
+    REGISTRY
+        .register(Box::new(ACCOUNT_DENOM_QUERY_STATUS_COLLECTOR.clone()))
+        .expect("collector can be registered");
+    REGISTRY
+        .register(Box::new(ACCOUNT_QUERY_ERRORS_COLLECTOR.clone()))
+        .expect("collector can be registered");
+    REGISTRY
+        .register(Box::new(GRPC_CHANNEL_CONNECTS_COLLECTOR.clone()))
+        .expect("collector can be registered");
+    REGISTRY
+        .register(Box::new(GRPC_CHANNEL_EVICTIONS_COLLECTOR.clone()))
+        .expect("collector can be registered");
 }
 
 pub async fn metrics_handler() -> Result<impl Reply, Rejection> {
@@ -105,3 +545,70 @@ pub async fn metrics_handler() -> Result<impl Reply, Rejection> {
     res.push_str(&res_custom);
     Ok(res)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removes_series_of_one_address() {
+        account_balance_setter("chain_T", "kept", "OM", "relayer", "", 1.0);
+        account_balance_setter("chain_T", "removed", "OM", "relayer", "", 1.0);
+        account_status_setter("chain_T", "removed", "OM", "1", "relayer", "", 0);
+        account_critical_status_setter("chain_T", "removed", "OM", "0.5", "relayer", "", 0);
+        account_query_errors_inc("chain_T", "removed", "relayer", "COSMOS", "timeout", "");
+
+        remove_account_series("chain_T", "removed", "relayer");
+
+        let balance = |address: &str| {
+            ACCOUNT_BALANCE_COLLECTOR
+                .get_metric_with_label_values(&["chain_T", address, "OM", "relayer", ""])
+                .unwrap()
+                .get()
+        };
+        assert_eq!(balance("kept"), 1.0);
+        // a removed series is recreated from zero on access
+        assert_eq!(balance("removed"), 0.0);
+        assert_eq!(
+            ACCOUNT_QUERY_ERRORS_COLLECTOR
+                .get_metric_with_label_values(&[
+                    "chain_T", "removed", "relayer", "COSMOS", "timeout", ""
+                ])
+                .unwrap()
+                .get(),
+            0
+        );
+    }
+
+    #[test]
+    fn expires_only_stale_gauges() {
+        account_query_status_setter("chain_T", "stale", "relayer", "", "https://a/", 0);
+        account_query_errors_inc(
+            "chain_T",
+            "stale",
+            "relayer",
+            "COSMOS",
+            "timeout",
+            "https://a/",
+        );
+
+        assert_eq!(
+            expire_account_series("chain_T", "stale", "relayer", Duration::from_secs(60)),
+            0
+        );
+        assert_eq!(
+            expire_account_series("chain_T", "stale", "relayer", Duration::ZERO),
+            1
+        );
+        let series = ACCOUNT_SERIES.lock().unwrap();
+        let remaining = &series[&(
+            "chain_T".to_string(),
+            "stale".to_string(),
+            "relayer".to_string(),
+        )];
+        assert_eq!(remaining.len(), 1);
+        assert!(remaining
+            .keys()
+            .all(|(collector, _)| *collector == AccountCollector::QueryErrors));
+    }
+}